@@ -0,0 +1,463 @@
+use super::instruction::PREFIX;
+use std::collections::HashMap;
+use std::fmt;
+
+/// Line of the source which could not be assembled
+#[derive(Debug, PartialEq, Eq)]
+pub struct AssemblyError {
+    /// Starting at 1
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AssemblyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {} : {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for AssemblyError {}
+
+const REGISTERS: [&str; 8] = ["B", "C", "D", "E", "H", "L", "[HL]", "A"];
+const WIDE_REGISTERS: [&str; 4] = ["BC", "DE", "HL", "SP"];
+const STACK_REGISTERS: [&str; 4] = ["BC", "DE", "HL", "AF"];
+const CONDITIONS: [&str; 4] = ["NZ", "Z", "NC", "C"];
+/// In the order of their opcodes, 0x80-0xBF and 0xC6-0xFE
+const ALU: [&str; 8] = ["ADD", "ADC", "SUB", "SBC", "AND", "XOR", "OR", "CP"];
+/// In the order of their prefixed opcodes, 0x00-0x3F
+const SHIFTS: [&str; 8] = ["RLC", "RRC", "RL", "RR", "SLA", "SRA", "SWAP", "SRL"];
+
+/// Assemble RGBDS-style source, to be mapped at `origin`.
+/// Supports labels, `DB` and `DW`, and `$` for the address of the current instruction.
+pub fn assemble(source: &str, origin: u16) -> Result<Vec<u8>, AssemblyError> {
+    // Sizes never depend on the labels: the first pass only collects them
+    let mut assembler = Assembler {
+        labels: HashMap::new(),
+        resolve: false,
+    };
+    assembler.pass(source, origin)?;
+    assembler.resolve = true;
+    assembler.pass(source, origin)
+}
+
+struct Assembler {
+    labels: HashMap<String, u16>,
+    /// Unknown labels are errors, instead of zeros
+    resolve: bool,
+}
+
+impl Assembler {
+    fn pass(&mut self, source: &str, origin: u16) -> Result<Vec<u8>, AssemblyError> {
+        let mut bytes = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let address = origin.wrapping_add(bytes.len() as u16);
+            let mut line = line.split(';').next().unwrap_or_default().trim();
+            if let Some(colon) = line.find(':') {
+                let label = line[..colon].trim();
+                if is_identifier(label) {
+                    self.labels.insert(label.to_string(), address);
+                    line = line[colon + 1..].trim();
+                }
+            }
+            if line.is_empty() {
+                continue;
+            }
+            let encoded = self.line(line, address).map_err(|message| AssemblyError {
+                line: index + 1,
+                message,
+            })?;
+            bytes.extend(encoded);
+        }
+        Ok(bytes)
+    }
+
+    /// Encode one instruction
+    fn line(&self, line: &str, address: u16) -> Result<Vec<u8>, String> {
+        let (mnemonic, operands) = match line.find(char::is_whitespace) {
+            Some(space) => (&line[..space], line[space..].trim()),
+            None => (line, ""),
+        };
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let mut operands: Vec<String> = if operands.is_empty() {
+            Vec::new()
+        } else {
+            operands.split(',').map(normalize).collect()
+        };
+        // The A of `ADD A,B` is optional for the 8 bits operations
+        if ALU.contains(&mnemonic.as_str()) && operands.len() == 2 && operands[0] == "A" {
+            operands.remove(0);
+        }
+        let operands: Vec<&str> = operands.iter().map(String::as_str).collect();
+        let unsupported = || format!("Unsupported operands for {} : {:?}", mnemonic, operands);
+
+        let opcode = match (mnemonic.as_str(), operands.as_slice()) {
+            ("DB", values) => {
+                return values
+                    .iter()
+                    .map(|value| self.byte(value, address))
+                    .collect()
+            }
+            ("DW", values) => {
+                let mut bytes = Vec::new();
+                for value in values {
+                    bytes.extend(self.word(value, address)?.to_le_bytes().iter());
+                }
+                return Ok(bytes);
+            }
+            ("NOP", []) => 0x00,
+            ("STOP", []) => return Ok(vec![0x10, 0x00]),
+            ("HALT", []) => 0x76,
+            ("DI", []) => 0xF3,
+            ("EI", []) => 0xFB,
+            ("DAA", []) => 0x27,
+            ("CPL", []) => 0x2F,
+            ("SCF", []) => 0x37,
+            ("CCF", []) => 0x3F,
+            ("RLCA", []) => 0x07,
+            ("RRCA", []) => 0x0F,
+            ("RLA", []) => 0x17,
+            ("RRA", []) => 0x1F,
+            ("RET", []) => 0xC9,
+            ("RETI", []) => 0xD9,
+            ("RET", [condition]) => 0xC0 | condition_index(condition).ok_or_else(unsupported)? << 3,
+            ("PUSH", [register]) => {
+                0xC5 | index(&STACK_REGISTERS, register).ok_or_else(unsupported)? << 4
+            }
+            ("POP", [register]) => {
+                0xC1 | index(&STACK_REGISTERS, register).ok_or_else(unsupported)? << 4
+            }
+            ("RST", [vector]) => {
+                let vector = self.byte(vector, address)?;
+                if vector & 0xC7 != 0 {
+                    return Err(format!("Invalid RST vector ${:02X}", vector));
+                }
+                0xC7 | vector
+            }
+            ("JP", ["HL"]) => 0xE9,
+            ("JP", [target]) => return self.with_word(0xC3, target, address),
+            ("JP", [condition, target]) => {
+                let condition = condition_index(condition).ok_or_else(unsupported)?;
+                return self.with_word(0xC2 | condition << 3, target, address);
+            }
+            ("CALL", [target]) => return self.with_word(0xCD, target, address),
+            ("CALL", [condition, target]) => {
+                let condition = condition_index(condition).ok_or_else(unsupported)?;
+                return self.with_word(0xC4 | condition << 3, target, address);
+            }
+            ("JR", [target]) => return self.relative(0x18, target, address),
+            ("JR", [condition, target]) => {
+                let condition = condition_index(condition).ok_or_else(unsupported)?;
+                return self.relative(0x20 | condition << 3, target, address);
+            }
+            ("INC", [target]) | ("DEC", [target]) => {
+                let dec = (mnemonic == "DEC") as u8;
+                if let Some(register) = index(&REGISTERS, target) {
+                    0x04 | register << 3 | dec
+                } else {
+                    let register = index(&WIDE_REGISTERS, target).ok_or_else(unsupported)?;
+                    0x03 | register << 4 | dec << 3
+                }
+            }
+            ("ADD", ["HL", register]) => {
+                0x09 | index(&WIDE_REGISTERS, register).ok_or_else(unsupported)? << 4
+            }
+            ("ADD", ["SP", offset]) => return Ok(vec![0xE8, self.signed(offset, address)?]),
+            (alu, [source]) if ALU.contains(&alu) => {
+                let operation = index(&ALU, alu).unwrap_or_default();
+                match index(&REGISTERS, source) {
+                    Some(register) => 0x80 | operation << 3 | register,
+                    None => return Ok(vec![0xC6 | operation << 3, self.byte(source, address)?]),
+                }
+            }
+            (shift, [target]) if SHIFTS.contains(&shift) => {
+                let operation = index(&SHIFTS, shift).unwrap_or_default();
+                let register = index(&REGISTERS, target).ok_or_else(unsupported)?;
+                return Ok(vec![PREFIX, operation << 3 | register]);
+            }
+            ("BIT", [bit, target]) | ("RES", [bit, target]) | ("SET", [bit, target]) => {
+                let base = match mnemonic.as_str() {
+                    "BIT" => 0x40,
+                    "RES" => 0x80,
+                    _ => 0xC0,
+                };
+                let bit = self.byte(bit, address)?;
+                if bit > 7 {
+                    return Err(format!("Invalid bit {}", bit));
+                }
+                let register = index(&REGISTERS, target).ok_or_else(unsupported)?;
+                return Ok(vec![PREFIX, base | bit << 3 | register]);
+            }
+            ("LDH", ["[C]", "A"]) | ("LD", ["[C]", "A"]) => 0xE2,
+            ("LDH", ["A", "[C]"]) | ("LD", ["A", "[C]"]) => 0xF2,
+            ("LDH", [target, "A"]) => return Ok(vec![0xE0, self.high_address(target, address)?]),
+            ("LDH", ["A", source]) => return Ok(vec![0xF0, self.high_address(source, address)?]),
+            ("LD", [target, source]) => return self.load(target, source, address),
+            _ => return Err(unsupported()),
+        };
+        Ok(vec![opcode])
+    }
+
+    fn load(&self, target: &str, source: &str, address: u16) -> Result<Vec<u8>, String> {
+        let opcode = match (target, source) {
+            ("[BC]", "A") => 0x02,
+            ("[DE]", "A") => 0x12,
+            ("[HL+]", "A") => 0x22,
+            ("[HL-]", "A") => 0x32,
+            ("A", "[BC]") => 0x0A,
+            ("A", "[DE]") => 0x1A,
+            ("A", "[HL+]") => 0x2A,
+            ("A", "[HL-]") => 0x3A,
+            ("SP", "HL") => 0xF9,
+            ("[HL]", "[HL]") => return Err("LD [HL],[HL] is HALT".to_string()),
+            ("HL", source) if source.starts_with("SP") => {
+                let offset = match &source[2..] {
+                    "" => 0,
+                    offset => self.signed(offset, address)?,
+                };
+                return Ok(vec![0xF8, offset]);
+            }
+            (target, "SP") if is_indirect(target) => {
+                return self.with_word(0x08, &target[1..target.len() - 1], address)
+            }
+            _ => match (index(&REGISTERS, target), index(&REGISTERS, source)) {
+                (Some(target), Some(source)) => 0x40 | target << 3 | source,
+                (Some(target), None) if !is_indirect(source) => {
+                    return Ok(vec![0x06 | target << 3, self.byte(source, address)?])
+                }
+                (Some(7), None) => {
+                    return self.with_word(0xFA, &source[1..source.len() - 1], address)
+                }
+                (None, Some(7)) if is_indirect(target) => {
+                    return self.with_word(0xEA, &target[1..target.len() - 1], address)
+                }
+                (None, None) => {
+                    let register = index(&WIDE_REGISTERS, target)
+                        .ok_or_else(|| format!("Unsupported LD {},{}", target, source))?;
+                    return self.with_word(0x01 | register << 4, source, address);
+                }
+                _ => return Err(format!("Unsupported LD {},{}", target, source)),
+            },
+        };
+        Ok(vec![opcode])
+    }
+
+    fn with_word(&self, opcode: u8, value: &str, address: u16) -> Result<Vec<u8>, String> {
+        let [low, high] = self.word(value, address)?.to_le_bytes();
+        Ok(vec![opcode, low, high])
+    }
+
+    /// JR to a label or an address, relative to the end of the instruction
+    fn relative(&self, opcode: u8, target: &str, address: u16) -> Result<Vec<u8>, String> {
+        let target = self.word(target, address)?;
+        let offset = target.wrapping_sub(address.wrapping_add(2)) as i16;
+        if self.resolve && (offset < i8::MIN as i16 || offset > i8::MAX as i16) {
+            return Err(format!("Jump to ${:04X} out of range", target));
+        }
+        Ok(vec![opcode, offset as u8])
+    }
+
+    /// `[$FF44]`, an address in 0xFF00-0xFFFF
+    fn high_address(&self, operand: &str, address: u16) -> Result<u8, String> {
+        if !is_indirect(operand) {
+            return Err(format!("Expected an address in brackets, got {}", operand));
+        }
+        let value = self.expression(&operand[1..operand.len() - 1], address)?;
+        match value {
+            0xFF00..=0xFFFF => Ok(value as u8),
+            // Zeros until the labels are known
+            _ if !self.resolve => Ok(0),
+            _ => Err(format!("${:04X} is not in high RAM", value)),
+        }
+    }
+
+    /// Signed or unsigned 16-bit value
+    fn word(&self, value: &str, address: u16) -> Result<u16, String> {
+        let value = self.expression(value, address)?;
+        if value < i16::MIN as i32 || value > u16::MAX as i32 {
+            return Err(format!("{} does not fit in a word", value));
+        }
+        Ok(value as u16)
+    }
+
+    /// Signed or unsigned 8-bit value
+    fn byte(&self, value: &str, address: u16) -> Result<u8, String> {
+        let value = self.expression(value, address)?;
+        if value < i8::MIN as i32 || value > u8::MAX as i32 {
+            return Err(format!("{} does not fit in a byte", value));
+        }
+        Ok(value as u8)
+    }
+
+    fn signed(&self, value: &str, address: u16) -> Result<u8, String> {
+        let value = self.expression(value, address)?;
+        if value < i8::MIN as i32 || value > i8::MAX as i32 {
+            return Err(format!("{} does not fit in a signed byte", value));
+        }
+        Ok(value as u8)
+    }
+
+    /// Sum of numbers and labels. `$` is the address of the current instruction.
+    fn expression(&self, expression: &str, address: u16) -> Result<i32, String> {
+        let mut total = 0i32;
+        let mut negative = false;
+        let mut term = String::new();
+        // Signs split the terms, except the ones of the `$` current address
+        let mut chars = expression.chars().peekable();
+        loop {
+            let next = chars.next();
+            match next {
+                Some('+') | Some('-') | None if !term.is_empty() => {
+                    let value = self.term(&term, address)?;
+                    total = if negative {
+                        total - i32::from(value)
+                    } else {
+                        total + i32::from(value)
+                    };
+                    term.clear();
+                    negative = next == Some('-');
+                }
+                Some('+') => {}
+                Some('-') => negative = !negative,
+                Some(c) => term.push(c),
+                None => return Err(format!("Missing value in {:?}", expression)),
+            }
+            if next.is_none() {
+                return Ok(total);
+            }
+        }
+    }
+
+    fn term(&self, term: &str, address: u16) -> Result<u16, String> {
+        let invalid = || format!("Invalid number {}", term);
+        if term == "$" {
+            Ok(address)
+        } else if let Some(hex) = term.strip_prefix('$') {
+            u16::from_str_radix(hex, 16).map_err(|_| invalid())
+        } else if let Some(hex) = term.strip_prefix("0X") {
+            u16::from_str_radix(hex, 16).map_err(|_| invalid())
+        } else if let Some(binary) = term.strip_prefix('%') {
+            u16::from_str_radix(binary, 2).map_err(|_| invalid())
+        } else if term.starts_with(|c: char| c.is_ascii_digit()) {
+            term.parse().map_err(|_| invalid())
+        } else if let Some(address) = self.labels.get(term) {
+            Ok(*address)
+        } else if self.resolve {
+            Err(format!("Unknown label {}", term))
+        } else {
+            Ok(0)
+        }
+    }
+}
+
+/// Upper case registers, keeping the case of the labels
+fn normalize(operand: &str) -> String {
+    let operand: String = operand.split_whitespace().collect();
+    let upper = operand.to_ascii_uppercase();
+    let is_keyword = REGISTERS.contains(&upper.as_str())
+        || WIDE_REGISTERS.contains(&upper.as_str())
+        || STACK_REGISTERS.contains(&upper.as_str())
+        || CONDITIONS.contains(&upper.as_str())
+        || ["[BC]", "[DE]", "[HL+]", "[HL-]", "[C]"].contains(&upper.as_str())
+        || upper.starts_with("SP")
+        || upper.starts_with("0X");
+    if is_keyword {
+        upper
+    } else {
+        operand
+    }
+}
+
+fn index(names: &[&str], name: &str) -> Option<u8> {
+    names.iter().position(|n| *n == name).map(|i| i as u8)
+}
+
+fn condition_index(condition: &str) -> Option<u8> {
+    index(&CONDITIONS, condition)
+}
+
+fn is_indirect(operand: &str) -> bool {
+    operand.starts_with('[') && operand.ends_with(']')
+}
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_' || c == '.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::disassembler::disassemble;
+
+    #[test]
+    fn program() {
+        let source = "
+            ; Count down from 3
+            start:
+                ld a, 3
+            .loop:
+                dec a
+                jr nz, .loop
+                ld [hl+], a
+                call start
+                db $DE, $AD
+                dw start
+        ";
+        assert_eq!(
+            assemble(source, 0xC000),
+            Ok(vec![
+                0x3E, 0x03, 0x3D, 0x20, 0xFD, 0x22, 0xCD, 0x00, 0xC0, 0xDE, 0xAD, 0x00, 0xC0
+            ])
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        let bytes = assemble("ld a, %1010\nld b, $FF\nld c, 0x10\nld d, -1\nld e, 7", 0);
+        assert_eq!(
+            bytes,
+            Ok(vec![
+                0x3E, 0x0A, 0x06, 0xFF, 0x0E, 0x10, 0x16, 0xFF, 0x1E, 0x07
+            ])
+        );
+        assert_eq!(assemble("jr $", 0), Ok(vec![0x18, 0xFE]));
+        assert_eq!(assemble("ld hl, sp - 2", 0), Ok(vec![0xF8, 0xFE]));
+        assert_eq!(assemble("ldh a, [$FF44]", 0), Ok(vec![0xF0, 0x44]));
+    }
+
+    #[test]
+    fn errors() {
+        let error = assemble("nop\nld a, unknown", 0).unwrap_err();
+        assert_eq!(error.line, 2);
+        assert!(assemble("ld a, 256", 0).is_err());
+        assert!(assemble("ld a, $FFFF", 0).is_err());
+        assert!(assemble("ld a, -129", 0).is_err());
+        assert!(assemble("ldh a, [$44]", 0).is_err());
+        assert!(assemble("bit 8, a", 0).is_err());
+        assert!(assemble("rst $39", 0).is_err());
+        assert!(assemble("jr far\nfar: nop", 0).is_ok());
+        assert!(assemble("jp [bc]", 0).is_err());
+    }
+
+    /// Every instruction printed by the disassembler assembles back to its bytes
+    #[test]
+    fn round_trip() {
+        for opcode in 0..=0xFFu8 {
+            // STOP is always followed by a zero
+            let operand = if opcode == 0x10 { 0x00 } else { 0x34 };
+            for bytes in [[opcode, operand, 0x12], [PREFIX, opcode, 0x00]].iter() {
+                let disassembled = disassemble(bytes, 0x0150).unwrap();
+                let length = disassembled.length as usize;
+                let text = disassembled.to_string();
+                assert_eq!(
+                    assemble(&text, 0x0150).as_deref(),
+                    Ok(&bytes[..length]),
+                    "{}",
+                    text
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use std::fmt;
+
+/// Header bytes covered by the header checksum
+const HEADER_CHECKSUM_START: usize = 0x0134;
+const HEADER_CHECKSUM_END: usize = 0x014C;
+pub const HEADER_CHECKSUM: usize = 0x014D;
+pub const GLOBAL_CHECKSUM: usize = 0x014E;
+
+/// Mismatch between the checksum declared in the header and the computed one
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ChecksumError {
+    /// 0x014D. Checked by the boot ROM, which locks up on mismatch.
+    Header { declared: u8, computed: u8 },
+    /// 0x014E-0x014F. Never checked by the hardware.
+    Global { declared: u16, computed: u16 },
+}
+
+impl fmt::Display for ChecksumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ChecksumError::Header { declared, computed } => write!(
+                f,
+                "Header checksum mismatch : declared 0x{:02x}, computed 0x{:02x}",
+                declared, computed
+            ),
+            ChecksumError::Global { declared, computed } => write!(
+                f,
+                "Global checksum mismatch : declared 0x{:04x}, computed 0x{:04x}",
+                declared, computed
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ChecksumError {}
+
+/// Checksum of the bytes 0x0134 to 0x014C, as computed by the boot ROM
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[HEADER_CHECKSUM_START..=HEADER_CHECKSUM_END]
+        .iter()
+        .fold(0u8, |checksum, &byte| {
+            checksum.wrapping_sub(byte).wrapping_sub(1)
+        })
+}
+
+/// Sum of all the ROM bytes, except the global checksum itself
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(index, _)| *index != GLOBAL_CHECKSUM && *index != GLOBAL_CHECKSUM + 1)
+        .fold(0u16, |checksum, (_, &byte)| {
+            checksum.wrapping_add(byte as u16)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_instrs_checksums() {
+        let rom = include_bytes!("../../../etc/cpu_instrs.gb");
+        assert_eq!(header_checksum(rom), rom[HEADER_CHECKSUM]);
+        // The test ROM ships with a wrong global checksum (0xF530)
+        assert_eq!(global_checksum(rom), 0xB171);
+    }
+
+    #[test]
+    fn empty_header_checksum() {
+        // 0 - 25 * 1
+        assert_eq!(header_checksum(&[0u8; 0x150]), 0xE7);
+    }
+}
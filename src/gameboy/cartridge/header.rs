@@ -0,0 +1,329 @@
+use super::checksum::{GLOBAL_CHECKSUM, HEADER_CHECKSUM};
+use crate::gameboy::{Error, GbResult};
+
+/// The header lives between 0x0100 and 0x014F
+pub const HEADER_END: usize = 0x014F;
+
+const TITLE_START: usize = 0x0134;
+/// Last byte of the title for old cartridges. Newer ones use it as CGB flag.
+const TITLE_END: usize = 0x0143;
+const NEW_LICENSEE_CODE: usize = 0x0144;
+const SGB_FLAG: usize = 0x0146;
+const CARTRIDGE_TYPE: usize = 0x0147;
+const ROM_SIZE: usize = 0x0148;
+const RAM_SIZE: usize = 0x0149;
+const DESTINATION_CODE: usize = 0x014A;
+const OLD_LICENSEE_CODE: usize = 0x014B;
+const VERSION_NUMBER: usize = 0x014C;
+
+/// Old licensee value telling to look at the new licensee code instead
+const USE_NEW_LICENSEE: u8 = 0x33;
+
+/// Chip soldered on the cartridge to extend the addressable ROM/RAM
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mapper {
+    RomOnly,
+    Mbc1,
+    Mbc2,
+    Mmm01,
+    Mbc3,
+    Mbc5,
+    Mbc6,
+    Mbc7,
+    PocketCamera,
+    BandaiTama5,
+    HuC3,
+    HuC1,
+}
+
+/// Cartridge type, read at 0x0147.
+/// Describes the mapper and the extra hardware present on the cartridge.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct CartridgeType {
+    /// raw value from the header
+    pub code: u8,
+    pub mapper: Mapper,
+    /// External RAM
+    pub ram: bool,
+    /// Battery keeping the external RAM (and timer) alive
+    pub battery: bool,
+    /// Real time clock
+    pub timer: bool,
+    pub rumble: bool,
+}
+
+impl CartridgeType {
+    fn new(code: u8, mapper: Mapper) -> Self {
+        Self {
+            code,
+            mapper,
+            ram: false,
+            battery: false,
+            timer: false,
+            rumble: false,
+        }
+    }
+
+    fn ram(mut self) -> Self {
+        self.ram = true;
+        self
+    }
+
+    fn battery(mut self) -> Self {
+        self.battery = true;
+        self
+    }
+
+    fn timer(mut self) -> Self {
+        self.timer = true;
+        self
+    }
+
+    fn rumble(mut self) -> Self {
+        self.rumble = true;
+        self
+    }
+
+    /// Decode the cartridge type byte. Returns None for undocumented values.
+    pub fn from_byte(code: u8) -> Option<Self> {
+        let new = |mapper| Self::new(code, mapper);
+        let cartridge_type = match code {
+            0x00 => new(Mapper::RomOnly),
+            0x01 => new(Mapper::Mbc1),
+            0x02 => new(Mapper::Mbc1).ram(),
+            0x03 => new(Mapper::Mbc1).ram().battery(),
+            0x05 => new(Mapper::Mbc2),
+            0x06 => new(Mapper::Mbc2).battery(),
+            0x08 => new(Mapper::RomOnly).ram(),
+            0x09 => new(Mapper::RomOnly).ram().battery(),
+            0x0B => new(Mapper::Mmm01),
+            0x0C => new(Mapper::Mmm01).ram(),
+            0x0D => new(Mapper::Mmm01).ram().battery(),
+            0x0F => new(Mapper::Mbc3).timer().battery(),
+            0x10 => new(Mapper::Mbc3).timer().ram().battery(),
+            0x11 => new(Mapper::Mbc3),
+            0x12 => new(Mapper::Mbc3).ram(),
+            0x13 => new(Mapper::Mbc3).ram().battery(),
+            0x19 => new(Mapper::Mbc5),
+            0x1A => new(Mapper::Mbc5).ram(),
+            0x1B => new(Mapper::Mbc5).ram().battery(),
+            0x1C => new(Mapper::Mbc5).rumble(),
+            0x1D => new(Mapper::Mbc5).rumble().ram(),
+            0x1E => new(Mapper::Mbc5).rumble().ram().battery(),
+            0x20 => new(Mapper::Mbc6),
+            0x22 => new(Mapper::Mbc7).rumble().ram().battery(),
+            0xFC => new(Mapper::PocketCamera),
+            0xFD => new(Mapper::BandaiTama5),
+            0xFE => new(Mapper::HuC3),
+            0xFF => new(Mapper::HuC1).ram().battery(),
+            _ => return None,
+        };
+        Some(cartridge_type)
+    }
+}
+
+/// CGB flag, read at 0x0143
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CgbSupport {
+    /// Plain GB game
+    None,
+    /// Game supports CGB functions, but works on old GB too
+    Compatible,
+    /// Game works on CGB only
+    Only,
+}
+
+/// Where the game is supposed to be sold, read at 0x014A
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Destination {
+    Japan,
+    Overseas,
+}
+
+/// Publisher of the game
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Licensee {
+    /// One byte code, read at 0x014B
+    Old(u8),
+    /// Two ASCII characters, read at 0x0144-0x0145
+    New(String),
+}
+
+/// Content of the cartridge header, from 0x0100 to 0x014F
+#[derive(Clone, Debug)]
+pub struct CartridgeHeader {
+    pub title: String,
+    pub licensee: Licensee,
+    pub cgb_support: CgbSupport,
+    /// Game supports SGB functions
+    pub sgb_support: bool,
+    pub cartridge_type: CartridgeType,
+    /// ROM size in bytes
+    pub rom_size: usize,
+    /// External RAM size in bytes
+    pub ram_size: usize,
+    pub destination: Destination,
+    pub version: u8,
+    pub header_checksum: u8,
+    pub global_checksum: u16,
+}
+
+impl CartridgeHeader {
+    /// Parse the header from the beginning of the ROM.
+    pub fn parse(rom: &[u8]) -> GbResult<Self> {
+        if rom.len() <= HEADER_END {
+            return Err(Error::RomTooSmall(rom.len()));
+        }
+
+        let cgb_support = match rom[TITLE_END] {
+            0x80 => CgbSupport::Compatible,
+            0xC0 => CgbSupport::Only,
+            _ => CgbSupport::None,
+        };
+
+        // The CGB flag steals the last byte of the title
+        let title_end = match cgb_support {
+            CgbSupport::None => TITLE_END + 1,
+            _ => TITLE_END,
+        };
+        let title = rom[TITLE_START..title_end]
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as char)
+            .collect();
+
+        let licensee = match rom[OLD_LICENSEE_CODE] {
+            USE_NEW_LICENSEE => Licensee::New(
+                rom[NEW_LICENSEE_CODE..=NEW_LICENSEE_CODE + 1]
+                    .iter()
+                    .map(|&c| c as char)
+                    .collect(),
+            ),
+            code => Licensee::Old(code),
+        };
+
+        let cartridge_type = CartridgeType::from_byte(rom[CARTRIDGE_TYPE])
+            .ok_or(Error::UnknownCartridgeType(rom[CARTRIDGE_TYPE]))?;
+
+        let rom_size = match rom[ROM_SIZE] {
+            // 32 KiB * (1 << value)
+            size @ 0x00..=0x08 => 0x8000 << size,
+            size => return Err(Error::UnknownRomSize(size)),
+        };
+
+        let ram_size = match rom[RAM_SIZE] {
+            0x00 => 0,
+            // Unused, but listed in various unofficial docs as 2KiB
+            0x01 => 0x800,
+            0x02 => 0x2000,
+            0x03 => 0x8000,
+            0x04 => 0x20000,
+            0x05 => 0x10000,
+            size => return Err(Error::UnknownRamSize(size)),
+        };
+
+        let destination = match rom[DESTINATION_CODE] {
+            0x00 => Destination::Japan,
+            _ => Destination::Overseas,
+        };
+
+        Ok(Self {
+            title,
+            licensee,
+            cgb_support,
+            sgb_support: rom[SGB_FLAG] == 0x03,
+            cartridge_type,
+            rom_size,
+            ram_size,
+            destination,
+            version: rom[VERSION_NUMBER],
+            header_checksum: rom[HEADER_CHECKSUM],
+            global_checksum: u16::from_be_bytes([rom[GLOBAL_CHECKSUM], rom[GLOBAL_CHECKSUM + 1]]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_rom() -> Vec<u8> {
+        let mut rom = vec![0u8; 0x8000];
+        rom[TITLE_START..TITLE_START + 6].copy_from_slice(b"TETRIS");
+        rom[OLD_LICENSEE_CODE] = 0x01;
+        rom[DESTINATION_CODE] = 0x01;
+        rom
+    }
+
+    #[test]
+    fn parse_rom_only() {
+        let header = CartridgeHeader::parse(&create_rom()).unwrap();
+        assert_eq!(header.title, "TETRIS");
+        assert_eq!(header.licensee, Licensee::Old(0x01));
+        assert_eq!(header.cgb_support, CgbSupport::None);
+        assert!(!header.sgb_support);
+        assert_eq!(header.cartridge_type.mapper, Mapper::RomOnly);
+        assert_eq!(header.rom_size, 0x8000);
+        assert_eq!(header.ram_size, 0);
+        assert_eq!(header.destination, Destination::Overseas);
+    }
+
+    #[test]
+    fn parse_new_licensee_and_flags() {
+        let mut rom = create_rom();
+        rom[OLD_LICENSEE_CODE] = USE_NEW_LICENSEE;
+        rom[NEW_LICENSEE_CODE..=NEW_LICENSEE_CODE + 1].copy_from_slice(b"01");
+        rom[TITLE_END] = 0xC0;
+        rom[SGB_FLAG] = 0x03;
+        rom[CARTRIDGE_TYPE] = 0x1B;
+        rom[ROM_SIZE] = 0x05;
+        rom[RAM_SIZE] = 0x03;
+        rom[DESTINATION_CODE] = 0x00;
+        rom[VERSION_NUMBER] = 0x02;
+
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        assert_eq!(header.licensee, Licensee::New("01".to_string()));
+        assert_eq!(header.cgb_support, CgbSupport::Only);
+        assert!(header.sgb_support);
+        assert_eq!(header.cartridge_type.mapper, Mapper::Mbc5);
+        assert!(header.cartridge_type.ram);
+        assert!(header.cartridge_type.battery);
+        assert!(!header.cartridge_type.rumble);
+        assert_eq!(header.rom_size, 1024 * 1024);
+        assert_eq!(header.ram_size, 32 * 1024);
+        assert_eq!(header.destination, Destination::Japan);
+        assert_eq!(header.version, 2);
+    }
+
+    #[test]
+    fn parse_cpu_instrs() {
+        let rom = include_bytes!("../../../etc/cpu_instrs.gb");
+        let header = CartridgeHeader::parse(rom).unwrap();
+        assert_eq!(header.title, "CPU_INSTRS");
+        assert_eq!(header.cgb_support, CgbSupport::Compatible);
+        assert_eq!(header.cartridge_type.mapper, Mapper::Mbc1);
+        assert_eq!(header.rom_size, rom.len());
+    }
+
+    #[test]
+    fn invalid_header() {
+        assert!(matches!(
+            CartridgeHeader::parse(&[0u8; 0x100]),
+            Err(Error::RomTooSmall(0x100))
+        ));
+
+        let mut rom = create_rom();
+        rom[CARTRIDGE_TYPE] = 0x42;
+        assert!(matches!(
+            CartridgeHeader::parse(&rom),
+            Err(Error::UnknownCartridgeType(0x42))
+        ));
+
+        let mut rom = create_rom();
+        rom[ROM_SIZE] = 0x52;
+        assert!(matches!(
+            CartridgeHeader::parse(&rom),
+            Err(Error::UnknownRomSize(0x52))
+        ));
+    }
+}
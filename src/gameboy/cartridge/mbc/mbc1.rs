@@ -0,0 +1,176 @@
+use super::{RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+/// MBC1 controller : up to 2MiB of ROM and 32KiB of RAM
+#[derive(Debug)]
+pub struct Mbc1 {
+    /// 0x0000-0x1FFF : 0x0A in the lower 4 bits enables the RAM
+    ram_enabled: bool,
+    /// 0x2000-0x3FFF : lower 5 bits of the ROM bank
+    rom_bank: u8,
+    /// 0x4000-0x5FFF : 2 bits, RAM bank or upper bits of the ROM bank
+    upper_bank: u8,
+    /// 0x6000-0x7FFF : in advanced mode, the upper bits also apply to 0x0000-0x3FFF and
+    /// to the RAM
+    advanced_banking: bool,
+    rom_bank_mask: usize,
+    ram_bank_mask: usize,
+}
+
+impl Mbc1 {
+    /// Bank numbers are masked with the number of banks, which are a power of 2
+    pub fn new(rom_banks: usize, ram_banks: usize) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 1,
+            upper_bank: 0,
+            advanced_banking: false,
+            rom_bank_mask: rom_banks.max(1) - 1,
+            ram_bank_mask: ram_banks.max(1) - 1,
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = value & 0x1F,
+            0x4000..=0x5FFF => self.upper_bank = value & 0x03,
+            0x6000..=0x7FFF => self.advanced_banking = (value & 0x01) == 0x01,
+            _ => unreachable!("MBC1 register out of range : {:04x}", address),
+        }
+    }
+
+    pub fn rom_offset(&self, address: u16) -> usize {
+        let (bank, local_address) = match address {
+            0x0000..=0x3FFF if self.advanced_banking => ((self.upper_bank as usize) << 5, address),
+            0x0000..=0x3FFF => (0, address),
+            _ => {
+                // Bank 0 can't be selected here, it reads as bank 1
+                let lower_bits = if self.rom_bank == 0 { 1 } else { self.rom_bank };
+                (
+                    ((self.upper_bank as usize) << 5) | lower_bits as usize,
+                    address - 0x4000,
+                )
+            }
+        };
+
+        (bank & self.rom_bank_mask) * ROM_BANK_SIZE + local_address as usize
+    }
+
+    fn ram_offset(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled {
+            return None;
+        }
+
+        let bank = if self.advanced_banking {
+            self.upper_bank as usize & self.ram_bank_mask
+        } else {
+            0
+        };
+        Some(bank * RAM_BANK_SIZE + (address - 0xA000) as usize)
+    }
+
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        self.ram_offset(address)
+            .and_then(|offset| ram.get(offset).copied())
+            .unwrap_or(0xFF)
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if let Some(byte) = self
+            .ram_offset(address)
+            .and_then(|offset| ram.get_mut(offset))
+        {
+            *byte = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ram_enable() {
+        let mut mbc = Mbc1::new(4, 1);
+        assert_eq!(mbc.ram_offset(0xA000), None);
+        mbc.write_register(0x0000, 0x0A);
+        assert_eq!(mbc.ram_offset(0xA000), Some(0));
+        mbc.write_register(0x1FFF, 0xFA);
+        assert_eq!(mbc.ram_offset(0xA010), Some(0x10));
+        mbc.write_register(0x0000, 0x00);
+        assert_eq!(mbc.ram_offset(0xA000), None);
+    }
+
+    #[test]
+    fn rom_bank() {
+        let mut mbc = Mbc1::new(128, 1);
+        // Bank 1 by default
+        assert_eq!(mbc.rom_offset(0x0042), 0x0042);
+        assert_eq!(mbc.rom_offset(0x4000), 0x4000);
+
+        mbc.write_register(0x2000, 0x05);
+        assert_eq!(mbc.rom_offset(0x4001), 5 * ROM_BANK_SIZE + 1);
+
+        // 0 is translated to 1
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        // Only 5 bits are used
+        mbc.write_register(0x3FFF, 0xE3);
+        assert_eq!(mbc.rom_offset(0x4000), 3 * ROM_BANK_SIZE);
+
+        // Upper bits
+        mbc.write_register(0x4000, 0x02);
+        assert_eq!(mbc.rom_offset(0x7FFF), 0x43 * ROM_BANK_SIZE + 0x3FFF);
+        // ... do not affect the first bank in simple mode
+        assert_eq!(mbc.rom_offset(0x0000), 0);
+    }
+
+    #[test]
+    fn rom_bank_zero_with_upper_bits() {
+        let mut mbc = Mbc1::new(128, 1);
+        mbc.write_register(0x4000, 0x01);
+        mbc.write_register(0x2000, 0x00);
+        // 0x20 can't be selected, 0x21 is selected instead
+        assert_eq!(mbc.rom_offset(0x4000), 0x21 * ROM_BANK_SIZE);
+    }
+
+    #[test]
+    fn rom_bank_masked() {
+        let mut mbc = Mbc1::new(4, 1);
+        mbc.write_register(0x2000, 0x06);
+        assert_eq!(mbc.rom_offset(0x4000), 2 * ROM_BANK_SIZE);
+    }
+
+    #[test]
+    fn advanced_banking() {
+        let mut mbc = Mbc1::new(128, 4);
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x4000, 0x02);
+
+        // Simple mode : first bank and RAM bank locked to 0
+        assert_eq!(mbc.rom_offset(0x0000), 0);
+        assert_eq!(mbc.ram_offset(0xA000), Some(0));
+
+        mbc.write_register(0x6000, 0x01);
+        assert_eq!(mbc.rom_offset(0x0000), 0x40 * ROM_BANK_SIZE);
+        assert_eq!(mbc.ram_offset(0xA001), Some(2 * RAM_BANK_SIZE + 1));
+
+        mbc.write_register(0x7FFF, 0x00);
+        assert_eq!(mbc.rom_offset(0x0000), 0);
+        assert_eq!(mbc.ram_offset(0xA001), Some(1));
+    }
+
+    #[test]
+    fn advanced_banking_small_rom() {
+        // 512KiB ROM : the upper bits only select the RAM bank
+        let mut mbc = Mbc1::new(32, 4);
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x6000, 0x01);
+        mbc.write_register(0x4000, 0x03);
+        mbc.write_register(0x2000, 0x04);
+        assert_eq!(mbc.rom_offset(0x0000), 0);
+        assert_eq!(mbc.rom_offset(0x4000), 4 * ROM_BANK_SIZE);
+        assert_eq!(mbc.ram_offset(0xA000), Some(3 * RAM_BANK_SIZE));
+    }
+}
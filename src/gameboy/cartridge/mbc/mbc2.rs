@@ -0,0 +1,121 @@
+use super::ROM_BANK_SIZE;
+
+/// Size of the RAM built in the MBC2 : 512 half-bytes
+pub const MBC2_RAM_SIZE: usize = 0x200;
+
+/// MBC2 controller : up to 256KiB of ROM and 512x4 bits of RAM inside the controller
+#[derive(Debug)]
+pub struct Mbc2 {
+    ram_enabled: bool,
+    /// 4 bits ROM bank
+    rom_bank: u8,
+    rom_bank_mask: usize,
+}
+
+impl Mbc2 {
+    pub fn new(rom_banks: usize) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 1,
+            rom_bank_mask: rom_banks.max(1) - 1,
+        }
+    }
+
+    /// Both registers live in 0x0000-0x3FFF, the bit 8 of the address selects which one
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x3FFF if address & 0x0100 == 0 => self.ram_enabled = (value & 0x0F) == 0x0A,
+            0x0000..=0x3FFF => {
+                self.rom_bank = match value & 0x0F {
+                    0 => 1,
+                    bank => bank,
+                }
+            }
+            0x4000..=0x7FFF => (),
+            _ => unreachable!("MBC2 register out of range : {:04x}", address),
+        }
+    }
+
+    pub fn rom_offset(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => address as usize,
+            _ => {
+                (self.rom_bank as usize & self.rom_bank_mask) * ROM_BANK_SIZE
+                    + (address - 0x4000) as usize
+            }
+        }
+    }
+
+    /// Only the 9 lower bits of the address are used, the RAM repeats in 0xA000-0xBFFF
+    fn ram_offset(address: u16) -> usize {
+        (address & 0x01FF) as usize
+    }
+
+    /// Only the lower 4 bits are stored, the upper ones read as 1
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+        0xF0 | ram.get(Self::ram_offset(address)).copied().unwrap_or(0x0F)
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+        if let Some(byte) = ram.get_mut(Self::ram_offset(address)) {
+            *byte = value & 0x0F;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_bank() {
+        let mut mbc = Mbc2::new(16);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        // Bit 8 set : ROM bank
+        mbc.write_register(0x2100, 0x0F);
+        assert_eq!(mbc.rom_offset(0x4001), 0x0F * ROM_BANK_SIZE + 1);
+        mbc.write_register(0x0100, 0xF3);
+        assert_eq!(mbc.rom_offset(0x4000), 0x03 * ROM_BANK_SIZE);
+
+        // 0 reads as 1
+        mbc.write_register(0x3FFF, 0x00);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        // Bit 8 clear : RAM enable, the bank is untouched
+        mbc.write_register(0x2000, 0x05);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+    }
+
+    #[test]
+    fn ram() {
+        let mut ram = vec![0u8; MBC2_RAM_SIZE];
+        let mut mbc = Mbc2::new(16);
+
+        mbc.write_ram(&mut ram, 0xA000, 0x0A);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+
+        // Bit 8 clear : RAM enable
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_ram(&mut ram, 0xA000, 0xAB);
+        assert_eq!(ram[0], 0x0B);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFB);
+
+        // Echoes every 512 bytes
+        assert_eq!(mbc.read_ram(&ram, 0xA200), 0xFB);
+        mbc.write_ram(&mut ram, 0xBFFF, 0x01);
+        assert_eq!(mbc.read_ram(&ram, 0xA1FF), 0xF1);
+
+        // Bit 8 set does not touch the RAM enable
+        mbc.write_register(0x0100, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFB);
+        mbc.write_register(0x1E00, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+    }
+}
@@ -0,0 +1,169 @@
+use super::rtc::Rtc;
+use super::{RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+/// MBC3 controller : up to 2MiB of ROM, 32KiB of RAM and an optional real time clock
+#[derive(Debug)]
+pub struct Mbc3 {
+    /// 0x0000-0x1FFF : 0x0A enables both the RAM and the clock registers
+    ram_enabled: bool,
+    /// 0x2000-0x3FFF : 7 bits ROM bank
+    rom_bank: u8,
+    /// 0x4000-0x5FFF : 0x00-0x03 selects a RAM bank, 0x08-0x0C a clock register
+    ram_bank: u8,
+    rtc: Option<Rtc>,
+    rom_bank_mask: usize,
+    ram_bank_mask: usize,
+}
+
+impl Mbc3 {
+    pub fn new(rom_banks: usize, ram_banks: usize, rtc: Option<Rtc>) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            rtc,
+            rom_bank_mask: rom_banks.max(1) - 1,
+            ram_bank_mask: ram_banks.max(1) - 1,
+        }
+    }
+
+    pub fn rtc(&self) -> Option<&Rtc> {
+        self.rtc.as_ref()
+    }
+
+    pub fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        self.rtc.as_mut()
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = (value & 0x0F) == 0x0A,
+            0x2000..=0x3FFF => self.rom_bank = value & 0x7F,
+            0x4000..=0x5FFF => self.ram_bank = value,
+            0x6000..=0x7FFF => {
+                if let Some(rtc) = self.rtc.as_mut() {
+                    rtc.write_latch(value)
+                }
+            }
+            _ => unreachable!("MBC3 register out of range : {:04x}", address),
+        }
+    }
+
+    pub fn rom_offset(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => address as usize,
+            _ => {
+                // Bank 0 can't be selected here, it reads as bank 1
+                let bank = if self.rom_bank == 0 { 1 } else { self.rom_bank };
+                (bank as usize & self.rom_bank_mask) * ROM_BANK_SIZE + (address - 0x4000) as usize
+            }
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> usize {
+        (self.ram_bank as usize & self.ram_bank_mask) * RAM_BANK_SIZE + (address - 0xA000) as usize
+    }
+
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        if !self.ram_enabled {
+            return 0xFF;
+        }
+
+        match (self.ram_bank, self.rtc.as_ref()) {
+            (0x00..=0x03, _) => ram.get(self.ram_offset(address)).copied().unwrap_or(0xFF),
+            (0x08..=0x0C, Some(rtc)) => rtc.read(self.ram_bank),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if !self.ram_enabled {
+            return;
+        }
+
+        match self.ram_bank {
+            0x00..=0x03 => {
+                if let Some(byte) = ram.get_mut(self.ram_offset(address)) {
+                    *byte = value;
+                }
+            }
+            0x08..=0x0C => {
+                if let Some(rtc) = self.rtc.as_mut() {
+                    rtc.write(self.ram_bank, value)
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::rtc::tests::FakeClock;
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn rom_bank() {
+        let mut mbc = Mbc3::new(128, 4, None);
+        assert_eq!(mbc.rom_offset(0x0042), 0x0042);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        mbc.write_register(0x2000, 0x7F);
+        assert_eq!(mbc.rom_offset(0x4001), 0x7F * ROM_BANK_SIZE + 1);
+
+        // 0 is translated to 1, there is no hole at 0x20, 0x40 and 0x60 unlike MBC1
+        mbc.write_register(0x2000, 0x00);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+        mbc.write_register(0x2000, 0x20);
+        assert_eq!(mbc.rom_offset(0x4000), 0x20 * ROM_BANK_SIZE);
+    }
+
+    #[test]
+    fn ram_bank() {
+        let mut ram = vec![0u8; 4 * RAM_BANK_SIZE];
+        let mut mbc = Mbc3::new(128, 4, None);
+
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+        assert_eq!(ram[0], 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x4000, 0x02);
+        mbc.write_ram(&mut ram, 0xA001, 0x42);
+        assert_eq!(ram[2 * RAM_BANK_SIZE + 1], 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0xA001), 0x42);
+
+        // No clock on this cartridge
+        mbc.write_register(0x4000, 0x08);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+    }
+
+    #[test]
+    fn rtc_registers() {
+        let clock = Arc::new(FakeClock::default());
+        let mut ram = vec![0u8; RAM_BANK_SIZE];
+        let mut mbc = Mbc3::new(128, 1, Some(Rtc::new(clock.clone())));
+        mbc.write_register(0x0000, 0x0A);
+
+        clock.advance(62);
+        mbc.write_register(0x6000, 0x00);
+        mbc.write_register(0x6000, 0x01);
+
+        mbc.write_register(0x4000, 0x08);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 2);
+        mbc.write_register(0x4000, 0x09);
+        assert_eq!(mbc.read_ram(&ram, 0xBFFF), 1);
+
+        // Set the hours
+        mbc.write_register(0x4000, 0x0A);
+        mbc.write_ram(&mut ram, 0xA000, 12);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 12);
+        // ... without touching the RAM
+        assert!(ram.iter().all(|&byte| byte == 0));
+
+        // Disabled
+        mbc.write_register(0x0000, 0x00);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+    }
+}
@@ -0,0 +1,155 @@
+use super::{RAM_BANK_SIZE, ROM_BANK_SIZE};
+
+/// MBC5 controller : up to 8MiB of ROM, 128KiB of RAM and an optional rumble motor
+#[derive(Debug)]
+pub struct Mbc5 {
+    /// 0x0000-0x1FFF : 0x0A enables the RAM
+    ram_enabled: bool,
+    /// 0x2000-0x2FFF : lower 8 bits, 0x3000-0x3FFF : 9th bit
+    rom_bank: u16,
+    /// 0x4000-0x5FFF : 4 bits RAM bank
+    ram_bank: u8,
+    /// On rumble cartridges, bit 3 of the RAM bank register drives the motor
+    has_rumble: bool,
+    rumble: bool,
+    rom_bank_mask: usize,
+    ram_bank_mask: usize,
+}
+
+impl Mbc5 {
+    pub fn new(rom_banks: usize, ram_banks: usize, has_rumble: bool) -> Self {
+        Self {
+            ram_enabled: false,
+            rom_bank: 1,
+            ram_bank: 0,
+            has_rumble,
+            rumble: false,
+            rom_bank_mask: rom_banks.max(1) - 1,
+            ram_bank_mask: ram_banks.max(1) - 1,
+        }
+    }
+
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled = value == 0x0A,
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            0x3000..=0x3FFF => {
+                self.rom_bank = (self.rom_bank & 0xFF) | (((value & 0x01) as u16) << 8)
+            }
+            0x4000..=0x5FFF if self.has_rumble => {
+                let rumble = value & 0x08 != 0;
+                if rumble != self.rumble {
+                    log::debug!("Rumble {}", if rumble { "on" } else { "off" });
+                }
+                self.rumble = rumble;
+                self.ram_bank = value & 0x07;
+            }
+            0x4000..=0x5FFF => self.ram_bank = value & 0x0F,
+            0x6000..=0x7FFF => (),
+            _ => unreachable!("MBC5 register out of range : {:04x}", address),
+        }
+    }
+
+    pub fn rom_offset(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => address as usize,
+            // Unlike the other controllers, bank 0 can be mapped here
+            _ => {
+                (self.rom_bank as usize & self.rom_bank_mask) * ROM_BANK_SIZE
+                    + (address - 0x4000) as usize
+            }
+        }
+    }
+
+    fn ram_offset(&self, address: u16) -> Option<usize> {
+        self.ram_enabled.then(|| {
+            (self.ram_bank as usize & self.ram_bank_mask) * RAM_BANK_SIZE
+                + (address - 0xA000) as usize
+        })
+    }
+
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        self.ram_offset(address)
+            .and_then(|offset| ram.get(offset).copied())
+            .unwrap_or(0xFF)
+    }
+
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        if let Some(byte) = self
+            .ram_offset(address)
+            .and_then(|offset| ram.get_mut(offset))
+        {
+            *byte = value;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rom_bank() {
+        let mut mbc = Mbc5::new(512, 1, false);
+        assert_eq!(mbc.rom_offset(0x4000), ROM_BANK_SIZE);
+
+        mbc.write_register(0x2000, 0xAB);
+        assert_eq!(mbc.rom_offset(0x4001), 0xAB * ROM_BANK_SIZE + 1);
+
+        // 9th bit
+        mbc.write_register(0x3000, 0xFF);
+        assert_eq!(mbc.rom_offset(0x4000), 0x1AB * ROM_BANK_SIZE);
+
+        // Lower bits are kept
+        mbc.write_register(0x3FFF, 0x00);
+        assert_eq!(mbc.rom_offset(0x4000), 0xAB * ROM_BANK_SIZE);
+
+        // Bank 0 is allowed
+        mbc.write_register(0x2FFF, 0x00);
+        assert_eq!(mbc.rom_offset(0x4000), 0);
+        assert_eq!(mbc.rom_offset(0x3FFF), 0x3FFF);
+    }
+
+    #[test]
+    fn rom_bank_masked() {
+        let mut mbc = Mbc5::new(64, 1, false);
+        mbc.write_register(0x3000, 0x01);
+        mbc.write_register(0x2000, 0x42);
+        assert_eq!(mbc.rom_offset(0x4000), 0x02 * ROM_BANK_SIZE);
+    }
+
+    #[test]
+    fn ram_bank() {
+        let mut ram = vec![0u8; 16 * RAM_BANK_SIZE];
+        let mut mbc = Mbc5::new(64, 16, false);
+
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+
+        // Only 0x0A enables the RAM
+        mbc.write_register(0x0000, 0x1A);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 0xFF);
+        mbc.write_register(0x0000, 0x0A);
+
+        mbc.write_register(0x4000, 0x0F);
+        mbc.write_ram(&mut ram, 0xA001, 0x42);
+        assert_eq!(ram[0x0F * RAM_BANK_SIZE + 1], 0x42);
+        assert_eq!(mbc.read_ram(&ram, 0xA001), 0x42);
+    }
+
+    #[test]
+    fn rumble() {
+        let mut ram = vec![0u8; 16 * RAM_BANK_SIZE];
+        let mut mbc = Mbc5::new(64, 16, true);
+        mbc.write_register(0x0000, 0x0A);
+
+        // Bit 3 drives the motor instead of selecting the bank
+        mbc.write_register(0x4000, 0x0B);
+        assert!(mbc.rumble);
+        mbc.write_ram(&mut ram, 0xA000, 0x42);
+        assert_eq!(ram[0x03 * RAM_BANK_SIZE], 0x42);
+
+        mbc.write_register(0x4000, 0x03);
+        assert!(!mbc.rumble);
+    }
+}
@@ -0,0 +1,159 @@
+/// MBC1 : up to 2MiB of ROM and 32KiB of RAM
+mod mbc1;
+/// MBC2 : up to 256KiB of ROM and 512x4 bits of built-in RAM
+mod mbc2;
+/// MBC3 : up to 2MiB of ROM, 32KiB of RAM and a real time clock
+mod mbc3;
+/// MBC5 : up to 8MiB of ROM, 128KiB of RAM and a rumble motor
+mod mbc5;
+/// Real time clock of the MBC3
+mod rtc;
+
+pub use mbc1::Mbc1;
+pub use mbc2::{Mbc2, MBC2_RAM_SIZE};
+pub use mbc3::Mbc3;
+pub use mbc5::Mbc5;
+pub use rtc::{Clock, Rtc, SystemClock};
+
+use std::sync::Arc;
+
+use super::{CartridgeHeader, Mapper};
+
+/// Size of a ROM bank, 0x4000-0x7FFF
+pub const ROM_BANK_SIZE: usize = 0x4000;
+/// Size of a RAM bank, 0xA000-0xBFFF
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/// Memory bank controller.
+/// Holds the registers written in 0x0000-0x7FFF and translates the addresses into offsets in
+/// the cartridge ROM and RAM.
+#[derive(Debug, Default)]
+pub enum Mbc {
+    /// 32KiB of ROM mapped directly, and optionally 8KiB of RAM
+    #[default]
+    None,
+    Mbc1(Mbc1),
+    Mbc2(Mbc2),
+    Mbc3(Mbc3),
+    Mbc5(Mbc5),
+}
+
+impl Mbc {
+    /// Controller matching the cartridge type, with the wall-clock as time source
+    pub fn new(header: &CartridgeHeader) -> Self {
+        Self::with_clock(header, Arc::new(SystemClock))
+    }
+
+    /// Controller matching the cartridge type, with a custom time source for the clock
+    pub fn with_clock(header: &CartridgeHeader, clock: Arc<dyn Clock>) -> Self {
+        let rom_banks = header.rom_size / ROM_BANK_SIZE;
+        let ram_banks = header.ram_size.div_ceil(RAM_BANK_SIZE);
+
+        match header.cartridge_type.mapper {
+            Mapper::Mbc1 => Mbc::Mbc1(Mbc1::new(rom_banks, ram_banks)),
+            Mapper::Mbc2 => Mbc::Mbc2(Mbc2::new(rom_banks)),
+            Mapper::Mbc3 => {
+                let rtc = header.cartridge_type.timer.then(|| Rtc::new(clock));
+                Mbc::Mbc3(Mbc3::new(rom_banks, ram_banks, rtc))
+            }
+            Mapper::Mbc5 => Mbc::Mbc5(Mbc5::new(
+                rom_banks,
+                ram_banks,
+                header.cartridge_type.rumble,
+            )),
+            _ => Mbc::None,
+        }
+    }
+
+    /// Real time clock of the cartridge, if any
+    pub fn rtc(&self) -> Option<&Rtc> {
+        match self {
+            Mbc::Mbc3(mbc) => mbc.rtc(),
+            _ => None,
+        }
+    }
+
+    pub fn rtc_mut(&mut self) -> Option<&mut Rtc> {
+        match self {
+            Mbc::Mbc3(mbc) => mbc.rtc_mut(),
+            _ => None,
+        }
+    }
+
+    /// Handle a write in the ROM area, 0x0000-0x7FFF
+    pub fn write_register(&mut self, address: u16, value: u8) {
+        match self {
+            Mbc::None => log::debug!("Ignored write to ROM {:04x} : {:02x}", address, value),
+            Mbc::Mbc1(mbc) => mbc.write_register(address, value),
+            Mbc::Mbc2(mbc) => mbc.write_register(address, value),
+            Mbc::Mbc3(mbc) => mbc.write_register(address, value),
+            Mbc::Mbc5(mbc) => mbc.write_register(address, value),
+        }
+    }
+
+    /// Offset in the cartridge ROM of the 0x0000-0x7FFF address
+    pub fn rom_offset(&self, address: u16) -> usize {
+        match self {
+            Mbc::None => address as usize,
+            Mbc::Mbc1(mbc) => mbc.rom_offset(address),
+            Mbc::Mbc2(mbc) => mbc.rom_offset(address),
+            Mbc::Mbc3(mbc) => mbc.rom_offset(address),
+            Mbc::Mbc5(mbc) => mbc.rom_offset(address),
+        }
+    }
+
+    /// Read the 0xA000-0xBFFF address. The controller maps it to the RAM or to its own
+    /// registers.
+    pub fn read_ram(&self, ram: &[u8], address: u16) -> u8 {
+        match self {
+            Mbc::None => ram
+                .get((address - 0xA000) as usize)
+                .copied()
+                .unwrap_or(0xFF),
+            Mbc::Mbc1(mbc) => mbc.read_ram(ram, address),
+            Mbc::Mbc2(mbc) => mbc.read_ram(ram, address),
+            Mbc::Mbc3(mbc) => mbc.read_ram(ram, address),
+            Mbc::Mbc5(mbc) => mbc.read_ram(ram, address),
+        }
+    }
+
+    /// Write the 0xA000-0xBFFF address
+    pub fn write_ram(&mut self, ram: &mut [u8], address: u16, value: u8) {
+        match self {
+            Mbc::None => {
+                if let Some(byte) = ram.get_mut((address - 0xA000) as usize) {
+                    *byte = value;
+                }
+            }
+            Mbc::Mbc1(mbc) => mbc.write_ram(ram, address, value),
+            Mbc::Mbc2(mbc) => mbc.write_ram(ram, address, value),
+            Mbc::Mbc3(mbc) => mbc.write_ram(ram, address, value),
+            Mbc::Mbc5(mbc) => mbc.write_ram(ram, address, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rtc::tests::FakeClock;
+    use super::*;
+
+    #[test]
+    fn mbc3_with_injected_clock() {
+        let mut rom = vec![0u8; 0x8000];
+        // MBC3+TIMER+RAM+BATTERY
+        rom[0x0147] = 0x10;
+        rom[0x0149] = 0x02;
+        let header = CartridgeHeader::parse(&rom).unwrap();
+        let clock = Arc::new(FakeClock::default());
+        let mut mbc = Mbc::with_clock(&header, clock.clone());
+        let ram = [0u8; RAM_BANK_SIZE];
+
+        clock.advance(3 * 60);
+        mbc.write_register(0x0000, 0x0A);
+        mbc.write_register(0x4000, 0x09);
+        mbc.write_register(0x6000, 0x00);
+        mbc.write_register(0x6000, 0x01);
+        assert_eq!(mbc.read_ram(&ram, 0xA000), 3);
+    }
+}
@@ -0,0 +1,343 @@
+use crate::gameboy::{Error, GbResult};
+use std::convert::TryInto;
+use std::fmt::Debug;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of time for the real time clock
+pub trait Clock: Debug + Send + Sync {
+    /// Seconds elapsed since an arbitrary, fixed, point in time
+    fn now(&self) -> u64;
+}
+
+/// Wall-clock time
+#[derive(Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)
+    }
+}
+
+const SECONDS_REGISTER: u8 = 0x08;
+const MINUTES_REGISTER: u8 = 0x09;
+const HOURS_REGISTER: u8 = 0x0A;
+const DAY_LOW_REGISTER: u8 = 0x0B;
+const DAY_HIGH_REGISTER: u8 = 0x0C;
+
+/// Clock state appended to the RAM in save files, as written by VBA-M, BGB, SameBoy...
+/// 5 running registers, 5 latched registers (one u32 each) then a UNIX timestamp, all
+/// little endian.
+pub const RTC_SAVE_SIZE: usize = 48;
+/// Older emulators write a 32 bits timestamp
+const RTC_SAVE_SIZE_32: usize = 44;
+/// The timestamp follows the 10 registers
+const RTC_TIMESTAMP: usize = 40;
+
+const DAY_HIGH_BIT: u8 = 0b0000_0001;
+const HALT_BIT: u8 = 0b0100_0000;
+const DAY_CARRY_BIT: u8 = 0b1000_0000;
+
+/// Values of the clock, as seen by the game
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct RtcRegisters {
+    pub seconds: u8,
+    pub minutes: u8,
+    pub hours: u8,
+    /// 9 bits day counter
+    pub days: u16,
+    /// The clock is stopped
+    pub halt: bool,
+    /// The day counter overflowed. Stays set until cleared by the game.
+    pub day_carry: bool,
+}
+
+impl RtcRegisters {
+    /// Add elapsed seconds, carrying to the next unit
+    fn advance(&mut self, seconds: u64) {
+        let seconds = self.seconds as u64 + seconds;
+        self.seconds = (seconds % 60) as u8;
+        let minutes = self.minutes as u64 + seconds / 60;
+        self.minutes = (minutes % 60) as u8;
+        let hours = self.hours as u64 + minutes / 60;
+        self.hours = (hours % 24) as u8;
+        let days = self.days as u64 + hours / 24;
+        if days > 0x1FF {
+            self.day_carry = true;
+        }
+        self.days = (days % 0x200) as u16;
+    }
+
+    fn read(&self, register: u8) -> u8 {
+        match register {
+            SECONDS_REGISTER => self.seconds,
+            MINUTES_REGISTER => self.minutes,
+            HOURS_REGISTER => self.hours,
+            DAY_LOW_REGISTER => self.days as u8,
+            DAY_HIGH_REGISTER => {
+                ((self.days >> 8) as u8 & DAY_HIGH_BIT)
+                    | if self.halt { HALT_BIT } else { 0 }
+                    | if self.day_carry { DAY_CARRY_BIT } else { 0 }
+            }
+            _ => 0xFF,
+        }
+    }
+
+    fn write(&mut self, register: u8, value: u8) {
+        match register {
+            SECONDS_REGISTER => self.seconds = value & 0x3F,
+            MINUTES_REGISTER => self.minutes = value & 0x3F,
+            HOURS_REGISTER => self.hours = value & 0x1F,
+            DAY_LOW_REGISTER => self.days = (self.days & 0x100) | value as u16,
+            DAY_HIGH_REGISTER => {
+                self.days = (self.days & 0xFF) | (((value & DAY_HIGH_BIT) as u16) << 8);
+                self.halt = value & HALT_BIT != 0;
+                self.day_carry = value & DAY_CARRY_BIT != 0;
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Real time clock of the MBC3.
+/// The game reads a latched copy of the registers, updated by writing 0x00 then 0x01 to
+/// 0x6000-0x7FFF.
+#[derive(Debug)]
+pub struct Rtc {
+    clock: Arc<dyn Clock>,
+    /// Running registers
+    registers: RtcRegisters,
+    /// Copy of the registers visible by the game
+    latched: RtcRegisters,
+    /// Clock time at which `registers` were last brought up to date
+    last_update: u64,
+    /// Last value written to the latch register
+    latch_value: u8,
+}
+
+impl Rtc {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        let last_update = clock.now();
+        Self {
+            clock,
+            registers: RtcRegisters::default(),
+            latched: RtcRegisters::default(),
+            last_update,
+            latch_value: 0xFF,
+        }
+    }
+
+    /// Running registers brought up to date with the clock source, and the current time
+    fn current(&self) -> (RtcRegisters, u64) {
+        let now = self.clock.now();
+        let mut registers = self.registers;
+        if !registers.halt {
+            registers.advance(now.saturating_sub(self.last_update));
+        }
+        (registers, now)
+    }
+
+    /// Bring the running registers up to date with the clock source
+    fn update(&mut self) {
+        let (registers, now) = self.current();
+        self.registers = registers;
+        self.last_update = now;
+    }
+
+    /// Serialize the clock in the save file format
+    pub fn save(&self) -> [u8; RTC_SAVE_SIZE] {
+        let (registers, now) = self.current();
+        let mut data = [0u8; RTC_SAVE_SIZE];
+        let both = [registers, self.latched];
+        let values = both.iter().flat_map(|registers| {
+            (SECONDS_REGISTER..=DAY_HIGH_REGISTER).map(move |register| registers.read(register))
+        });
+        for (chunk, value) in data.chunks_exact_mut(4).zip(values) {
+            chunk.copy_from_slice(&(value as u32).to_le_bytes());
+        }
+        data[RTC_TIMESTAMP..].copy_from_slice(&now.to_le_bytes());
+        data
+    }
+
+    /// Restore the clock from a save file. The time elapsed since the save is added on the
+    /// next update.
+    pub fn load(&mut self, data: &[u8]) -> GbResult<()> {
+        let timestamp = match data.len() {
+            RTC_SAVE_SIZE => u64::from_le_bytes(data[RTC_TIMESTAMP..].try_into().unwrap()),
+            RTC_SAVE_SIZE_32 => {
+                u32::from_le_bytes(data[RTC_TIMESTAMP..].try_into().unwrap()) as u64
+            }
+            size => return Err(Error::InvalidSaveSize(size)),
+        };
+        let mut values = data[..RTC_TIMESTAMP]
+            .chunks_exact(4)
+            .map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()) as u8);
+        for registers in [&mut self.registers, &mut self.latched].iter_mut() {
+            for register in SECONDS_REGISTER..=DAY_HIGH_REGISTER {
+                registers.write(register, values.next().unwrap());
+            }
+        }
+        self.last_update = timestamp;
+        Ok(())
+    }
+
+    /// Write to 0x6000-0x7FFF
+    pub fn write_latch(&mut self, value: u8) {
+        if self.latch_value == 0x00 && value == 0x01 {
+            self.update();
+            self.latched = self.registers;
+        }
+        self.latch_value = value;
+    }
+
+    /// Read the latched value of the register selected by 0x08-0x0C
+    pub fn read(&self, register: u8) -> u8 {
+        self.latched.read(register)
+    }
+
+    /// Write the register selected by 0x08-0x0C
+    pub fn write(&mut self, register: u8, value: u8) {
+        self.update();
+        self.registers.write(register, value);
+        // The write is also visible without latching again
+        self.latched.write(register, value);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Clock moved forward manually
+    #[derive(Debug, Default)]
+    pub struct FakeClock(AtomicU64);
+
+    impl FakeClock {
+        pub fn advance(&self, seconds: u64) {
+            self.0.fetch_add(seconds, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+
+    fn latch(rtc: &mut Rtc) {
+        rtc.write_latch(0x00);
+        rtc.write_latch(0x01);
+    }
+
+    #[test]
+    fn latch_time() {
+        let clock = Arc::new(FakeClock::default());
+        let mut rtc = Rtc::new(clock.clone());
+
+        clock.advance(3 * 3600 + 2 * 60 + 1);
+        // Not latched yet
+        assert_eq!(rtc.read(SECONDS_REGISTER), 0);
+
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS_REGISTER), 1);
+        assert_eq!(rtc.read(MINUTES_REGISTER), 2);
+        assert_eq!(rtc.read(HOURS_REGISTER), 3);
+
+        // Latched values do not move
+        clock.advance(10);
+        assert_eq!(rtc.read(SECONDS_REGISTER), 1);
+
+        // Writing 0x01 alone does not latch
+        rtc.write_latch(0x01);
+        assert_eq!(rtc.read(SECONDS_REGISTER), 1);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS_REGISTER), 11);
+    }
+
+    #[test]
+    fn day_counter_and_carry() {
+        let clock = Arc::new(FakeClock::default());
+        let mut rtc = Rtc::new(clock.clone());
+
+        clock.advance(300 * 24 * 3600);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(DAY_LOW_REGISTER), (300 & 0xFF) as u8);
+        assert_eq!(rtc.read(DAY_HIGH_REGISTER), DAY_HIGH_BIT);
+
+        clock.advance(300 * 24 * 3600);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(DAY_LOW_REGISTER), (600 - 512) as u8);
+        assert_eq!(rtc.read(DAY_HIGH_REGISTER), DAY_CARRY_BIT);
+
+        // The carry stays until cleared
+        clock.advance(24 * 3600);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(DAY_HIGH_REGISTER), DAY_CARRY_BIT);
+        rtc.write(DAY_HIGH_REGISTER, 0x00);
+        assert_eq!(rtc.read(DAY_HIGH_REGISTER), 0x00);
+    }
+
+    #[test]
+    fn halt() {
+        let clock = Arc::new(FakeClock::default());
+        let mut rtc = Rtc::new(clock.clone());
+
+        clock.advance(5);
+        rtc.write(DAY_HIGH_REGISTER, HALT_BIT);
+        clock.advance(100);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS_REGISTER), 5);
+        assert_eq!(rtc.read(DAY_HIGH_REGISTER), HALT_BIT);
+
+        // Restart the clock, after setting the time
+        rtc.write(SECONDS_REGISTER, 50);
+        rtc.write(MINUTES_REGISTER, 59);
+        rtc.write(HOURS_REGISTER, 23);
+        rtc.write(DAY_HIGH_REGISTER, 0x00);
+        clock.advance(10);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS_REGISTER), 0);
+        assert_eq!(rtc.read(MINUTES_REGISTER), 0);
+        assert_eq!(rtc.read(HOURS_REGISTER), 0);
+        assert_eq!(rtc.read(DAY_LOW_REGISTER), 1);
+    }
+
+    #[test]
+    fn save_and_load() {
+        let clock = Arc::new(FakeClock::default());
+        clock.advance(1_000_000);
+        let mut rtc = Rtc::new(clock.clone());
+
+        clock.advance(2 * 60 + 1);
+        latch(&mut rtc);
+        clock.advance(1);
+        let data = rtc.save();
+        assert_eq!(data[0..4], [2, 0, 0, 0]);
+        assert_eq!(data[4..8], [2, 0, 0, 0]);
+        // Latched seconds
+        assert_eq!(data[20..24], [1, 0, 0, 0]);
+        assert_eq!(data[40..48], (1_000_000u64 + 122).to_le_bytes());
+
+        // Time keeps going while the emulator is off
+        clock.advance(60);
+        let mut rtc = Rtc::new(clock.clone());
+        rtc.load(&data).unwrap();
+        assert_eq!(rtc.read(SECONDS_REGISTER), 1);
+        latch(&mut rtc);
+        assert_eq!(rtc.read(SECONDS_REGISTER), 2);
+        assert_eq!(rtc.read(MINUTES_REGISTER), 3);
+
+        // 32 bits timestamp
+        let mut rtc = Rtc::new(clock.clone());
+        rtc.load(&data[..RTC_SAVE_SIZE_32]).unwrap();
+        latch(&mut rtc);
+        assert_eq!(rtc.read(MINUTES_REGISTER), 3);
+
+        assert!(rtc.load(&data[..10]).is_err());
+    }
+}
@@ -0,0 +1,179 @@
+use super::mbc::{Mbc, MBC2_RAM_SIZE, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use super::{Cartridge, Mapper};
+use crate::gameboy::GbResult;
+
+/// Cartridge as seen from the memory bus : ROM in 0x0000-0x7FFF and RAM in 0xA000-0xBFFF,
+/// both going through the memory bank controller.
+#[derive(Debug)]
+pub struct CartridgeMemory {
+    /// Whole cartridge ROM, the controller picks the visible banks
+    rom: Vec<u8>,
+    /// Whole cartridge RAM, or the RAM built in the controller for MBC2
+    ram: Vec<u8>,
+    mbc: Mbc,
+    /// The RAM and clock are kept alive by a battery, and saved between runs
+    battery: bool,
+}
+
+/// No cartridge inserted : 32KiB of ROM and 8KiB of RAM mapped directly
+impl Default for CartridgeMemory {
+    fn default() -> Self {
+        Self {
+            rom: vec![0u8; 2 * ROM_BANK_SIZE],
+            ram: vec![0u8; RAM_BANK_SIZE],
+            mbc: Mbc::default(),
+            battery: false,
+        }
+    }
+}
+
+impl CartridgeMemory {
+    pub fn new(cartridge: &Cartridge) -> Self {
+        let header = cartridge.header();
+        let ram_size = match header.cartridge_type.mapper {
+            // The header declares no RAM, it lives in the controller
+            Mapper::Mbc2 => MBC2_RAM_SIZE,
+            _ => header.ram_size,
+        };
+        Self {
+            rom: cartridge.rom().to_vec(),
+            ram: vec![0u8; ram_size],
+            mbc: Mbc::new(header),
+            battery: header.cartridge_type.battery,
+        }
+    }
+
+    /// Read the 0x0000-0x7FFF address. Out of the ROM, the bus is floating.
+    pub fn read_rom(&self, address: u16) -> u8 {
+        self.rom
+            .get(self.mbc.rom_offset(address))
+            .copied()
+            .unwrap_or(0xFF)
+    }
+
+    /// Writing to the ROM sets the memory bank controller registers
+    pub fn write_rom(&mut self, address: u16, value: u8) {
+        self.mbc.write_register(address, value)
+    }
+
+    /// Read the 0xA000-0xBFFF address
+    pub fn read_ram(&self, address: u16) -> u8 {
+        self.mbc.read_ram(&self.ram, address)
+    }
+
+    /// Write the 0xA000-0xBFFF address
+    pub fn write_ram(&mut self, address: u16, value: u8) {
+        self.mbc.write_ram(&mut self.ram, address, value)
+    }
+
+    /// Content of the save file : the raw RAM, followed by the clock state if any.
+    /// None when nothing survives a power off.
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        if !self.battery {
+            return None;
+        }
+        let mut data = self.ram.clone();
+        if let Some(rtc) = self.mbc.rtc() {
+            data.extend_from_slice(&rtc.save());
+        }
+        Some(data)
+    }
+
+    /// Restore the RAM and the clock from a save file
+    pub fn load_save(&mut self, data: &[u8]) -> GbResult<()> {
+        let ram_size = self.ram.len().min(data.len());
+        if ram_size < self.ram.len() {
+            log::warn!(
+                "Save contains {} bytes, expected {} bytes of RAM",
+                data.len(),
+                self.ram.len()
+            );
+        }
+        self.ram[..ram_size].copy_from_slice(&data[..ram_size]);
+
+        let clock = &data[ram_size..];
+        match self.mbc.rtc_mut() {
+            // Saves from emulators without clock support
+            Some(_) if clock.is_empty() => log::warn!("Save without clock state"),
+            Some(rtc) => rtc.load(clock)?,
+            None if !clock.is_empty() => {
+                log::warn!("Ignored {} trailing bytes in save", clock.len())
+            }
+            None => (),
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn battery_save() {
+        let mut rom = vec![0u8; 0x8000];
+        // MBC3+TIMER+RAM+BATTERY, 8KiB
+        rom[0x0147] = 0x10;
+        rom[0x0149] = 0x02;
+        let cartridge = Cartridge::from_bytes(rom).unwrap();
+        let mut memory = CartridgeMemory::new(&cartridge);
+        memory.write_rom(0x0000, 0x0A);
+        memory.write_ram(0xA001, 0x42);
+
+        let data = memory.save_data().unwrap();
+        // RAM, then 48 bytes of clock
+        assert_eq!(data.len(), RAM_BANK_SIZE + 48);
+        assert_eq!(data[1], 0x42);
+
+        let mut memory = CartridgeMemory::new(&cartridge);
+        memory.load_save(&data).unwrap();
+        memory.write_rom(0x0000, 0x0A);
+        assert_eq!(memory.read_ram(0xA001), 0x42);
+
+        // Save without the clock
+        let mut memory = CartridgeMemory::new(&cartridge);
+        memory.load_save(&data[..RAM_BANK_SIZE]).unwrap();
+        assert!(memory.load_save(&data[..RAM_BANK_SIZE + 3]).is_err());
+    }
+
+    #[test]
+    fn no_battery() {
+        let mut rom = vec![0u8; 0x8000];
+        // MBC1+RAM
+        rom[0x0147] = 0x02;
+        rom[0x0149] = 0x02;
+        let memory = CartridgeMemory::new(&Cartridge::from_bytes(rom).unwrap());
+        assert!(memory.save_data().is_none());
+    }
+
+    #[test]
+    fn mbc2_built_in_ram() {
+        let mut rom = vec![0u8; 0x40000];
+        // MBC2+BATTERY, 256KiB
+        rom[0x0147] = 0x06;
+        rom[0x0148] = 0x03;
+        rom[0x0F * ROM_BANK_SIZE] = 0x42;
+        let mut memory = CartridgeMemory::new(&Cartridge::from_bytes(rom).unwrap());
+
+        memory.write_rom(0x2100, 0x0F);
+        assert_eq!(memory.read_rom(0x4000), 0x42);
+
+        memory.write_rom(0x0000, 0x0A);
+        memory.write_ram(0xA1FF, 0x05);
+        assert_eq!(memory.read_ram(0xBFFF), 0xF5);
+    }
+
+    #[test]
+    fn mbc5_large_rom() {
+        let mut rom = vec![0u8; 0x800000];
+        // MBC5, 8MiB
+        rom[0x0147] = 0x19;
+        rom[0x0148] = 0x08;
+        rom[0x1FF * ROM_BANK_SIZE] = 0x42;
+        let mut memory = CartridgeMemory::new(&Cartridge::from_bytes(rom).unwrap());
+
+        memory.write_rom(0x2000, 0xFF);
+        memory.write_rom(0x3000, 0x01);
+        assert_eq!(memory.read_rom(0x4000), 0x42);
+    }
+}
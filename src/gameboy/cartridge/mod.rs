@@ -0,0 +1,209 @@
+/// Header and global checksums
+mod checksum;
+/// Cartridge header parsing
+mod header;
+/// Memory bank controllers
+mod mbc;
+/// ROM and RAM of the cartridge behind its controller
+mod memory;
+/// Battery backed save files
+mod save;
+
+pub use checksum::ChecksumError;
+pub use header::{CartridgeHeader, CartridgeType, CgbSupport, Destination, Licensee, Mapper};
+pub(crate) use memory::CartridgeMemory;
+pub use save::SaveFile;
+
+use checksum::{global_checksum, header_checksum, GLOBAL_CHECKSUM, HEADER_CHECKSUM};
+
+use super::{Error, GbResult};
+use std::fs;
+
+/// Game cartridge : the raw ROM and its parsed header
+#[derive(Debug)]
+pub struct Cartridge {
+    header: CartridgeHeader,
+    rom: Vec<u8>,
+}
+
+impl Cartridge {
+    /// Read and parse the ROM file
+    pub fn from_file(rom_path: &str) -> GbResult<Self> {
+        let rom = fs::read(rom_path).map_err(|source| Error::Io {
+            path: rom_path.into(),
+            source,
+        })?;
+        Self::from_bytes(rom)
+    }
+
+    /// Parse the header of an in-memory ROM
+    pub fn from_bytes(rom: Vec<u8>) -> GbResult<Self> {
+        let header = CartridgeHeader::parse(&rom)?;
+
+        if header.rom_size != rom.len() {
+            log::warn!(
+                "Header declares {} bytes of ROM but the file contains {} bytes",
+                header.rom_size,
+                rom.len()
+            );
+        }
+
+        Ok(Self { header, rom })
+    }
+
+    pub fn header(&self) -> &CartridgeHeader {
+        &self.header
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    /// Compare the checksums declared in the header to the computed ones
+    pub fn checksum_errors(&self) -> Vec<ChecksumError> {
+        let mut errors = Vec::new();
+
+        let computed = header_checksum(&self.rom);
+        if computed != self.header.header_checksum {
+            errors.push(ChecksumError::Header {
+                declared: self.header.header_checksum,
+                computed,
+            });
+        }
+
+        let computed = global_checksum(&self.rom);
+        if computed != self.header.global_checksum {
+            errors.push(ChecksumError::Global {
+                declared: self.header.global_checksum,
+                computed,
+            });
+        }
+
+        errors
+    }
+
+    /// Overwrite the declared checksums with the computed ones, in the ROM and the header.
+    /// The header checksum must be fixed first as it is part of the global checksum.
+    pub fn fix_checksums(&mut self) {
+        let checksum = header_checksum(&self.rom);
+        self.rom[HEADER_CHECKSUM] = checksum;
+        self.header.header_checksum = checksum;
+
+        let checksum = global_checksum(&self.rom);
+        self.rom[GLOBAL_CHECKSUM..=GLOBAL_CHECKSUM + 1].copy_from_slice(&checksum.to_be_bytes());
+        self.header.global_checksum = checksum;
+    }
+
+    /// Check that the emulator is able to run this cartridge
+    pub fn check_supported(&self) -> GbResult<()> {
+        let cartridge_type = &self.header.cartridge_type;
+        match cartridge_type.mapper {
+            Mapper::RomOnly | Mapper::Mbc1 | Mapper::Mbc2 | Mapper::Mbc3 | Mapper::Mbc5 => (),
+            mapper => {
+                return Err(Error::UnsupportedMapper {
+                    code: cartridge_type.code,
+                    mapper,
+                })
+            }
+        }
+
+        if self.header.cgb_support == CgbSupport::Only {
+            return Err(Error::CgbOnly {
+                title: self.header.title.clone(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn create_rom(cartridge_type: u8) -> Vec<u8> {
+        let mut rom = vec![0u8; 0x8000];
+        rom[0x0147] = cartridge_type;
+        rom
+    }
+
+    #[test]
+    fn rom_only_is_supported() {
+        let cartridge = Cartridge::from_bytes(create_rom(0x00)).unwrap();
+        assert!(cartridge.check_supported().is_ok());
+    }
+
+    #[test]
+    fn unsupported_mapper() {
+        let cartridge = Cartridge::from_bytes(create_rom(0xFC)).unwrap();
+        assert_eq!(
+            cartridge.header().cartridge_type.mapper,
+            Mapper::PocketCamera
+        );
+        assert!(matches!(
+            cartridge.check_supported(),
+            Err(Error::UnsupportedMapper {
+                code: 0xFC,
+                mapper: Mapper::PocketCamera
+            })
+        ));
+    }
+
+    #[test]
+    fn missing_file() {
+        assert!(matches!(
+            Cartridge::from_file("missing.gb"),
+            Err(Error::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn checksums() {
+        let cartridge = Cartridge::from_bytes(create_rom(0x00)).unwrap();
+        assert_eq!(
+            cartridge.checksum_errors(),
+            vec![ChecksumError::Header {
+                declared: 0x00,
+                computed: 0xE7
+            }]
+        );
+
+        let cartridge =
+            Cartridge::from_bytes(include_bytes!("../../../etc/cpu_instrs.gb").to_vec()).unwrap();
+        assert_eq!(
+            cartridge.checksum_errors(),
+            vec![ChecksumError::Global {
+                declared: 0xF530,
+                computed: 0xB171
+            }]
+        );
+    }
+
+    #[test]
+    fn fix_checksums() {
+        let mut rom = create_rom(0x00);
+        rom[0x0200] = 0x42;
+        rom[0x014F] = 0x24;
+        let mut cartridge = Cartridge::from_bytes(rom).unwrap();
+        assert_eq!(cartridge.checksum_errors().len(), 2);
+
+        cartridge.fix_checksums();
+        assert!(cartridge.checksum_errors().is_empty());
+        assert_eq!(cartridge.header().header_checksum, 0xE7);
+        assert_eq!(cartridge.rom()[0x014D], 0xE7);
+        // 0x42 + 0xE7
+        assert_eq!(cartridge.header().global_checksum, 0x0129);
+        assert_eq!(cartridge.rom()[0x014E..=0x014F], [0x01, 0x29]);
+    }
+
+    #[test]
+    fn cgb_only() {
+        let mut rom = create_rom(0x00);
+        rom[0x0143] = 0xC0;
+        let cartridge = Cartridge::from_bytes(rom).unwrap();
+        assert!(matches!(
+            cartridge.check_supported(),
+            Err(Error::CgbOnly { .. })
+        ));
+    }
+}
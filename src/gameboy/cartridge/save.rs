@@ -0,0 +1,80 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Battery save of a cartridge, stored next to the ROM with the `.sav` extension.
+/// The content is the raw RAM, followed by the clock state for MBC3 cartridges with a timer.
+#[derive(Debug)]
+pub struct SaveFile {
+    path: PathBuf,
+    /// Last content written, to skip writing the same data again
+    last_saved: Option<Vec<u8>>,
+}
+
+impl SaveFile {
+    /// Save file of the given ROM
+    pub fn for_rom(rom_path: &str) -> Self {
+        Self {
+            path: Path::new(rom_path).with_extension("sav"),
+            last_saved: None,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Read the save, None if there is none yet
+    pub fn read(&mut self) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(&self.path) {
+            Ok(data) => {
+                self.last_saved = Some(data.clone());
+                Ok(Some(data))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Write the save if it changed since the last write.
+    /// Data goes to a temporary file first, renamed over the save once complete, so a crash
+    /// never leaves a truncated save behind.
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        if self.last_saved.as_deref() == Some(data) {
+            return Ok(());
+        }
+
+        let temp_path = self.path.with_extension("sav.tmp");
+        let mut file = File::create(&temp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &self.path)?;
+
+        self.last_saved = Some(data.to_vec());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_read() {
+        let directory = std::env::temp_dir().join(format!("gb-save-{}", std::process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        let rom_path = directory.join("game.gb");
+
+        let mut save = SaveFile::for_rom(rom_path.to_str().unwrap());
+        assert_eq!(save.path(), directory.join("game.sav"));
+        assert_eq!(save.read().unwrap(), None);
+
+        save.write(&[1, 2, 3]).unwrap();
+        assert!(!directory.join("game.sav.tmp").exists());
+
+        let mut save = SaveFile::for_rom(rom_path.to_str().unwrap());
+        assert_eq!(save.read().unwrap(), Some(vec![1, 2, 3]));
+
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
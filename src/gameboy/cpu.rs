@@ -1,7 +1,10 @@
 use super::arithmetictarget::{ArithmeticTarget, WideArithmeticTarget};
-use super::instruction::{Instruction, JumpTest, JumpType};
+use super::disassembler;
+use super::instruction::{Instruction, JumpTest, JumpType, PREFIX};
 use super::memory::SharedMemory;
 use super::registers::Registers;
+use super::Model;
+use std::io::Write;
 use std::ops::Not;
 
 type Delay = u32;
@@ -9,13 +12,32 @@ type ProgramCounter = u16;
 
 /// Custom type to split the ProgramCounter and the time offset / number of cycle of the CPU
 type CpuEffect = (ProgramCounter, Delay);
-const NO_CPU_EFFECT: CpuEffect = (0, 0);
+/// Two wait states, two pushes and the jump to the vector
+const INTERRUPT_DISPATCH_DELAY: Delay = 20;
+/// A locked CPU still lets the rest of the system run, one M-cycle at a time
+const LOCKED_DELAY: Delay = 4;
+/// A halted or stopped CPU checks its wake up condition every M-cycle
+const HALTED_DELAY: Delay = 4;
 
 pub struct Cpu {
     registers: Registers,
     pc: ProgramCounter,
     sp: u16,
     is_halted: bool,
+    /// HALT with IME off and an interrupt pending: PC is not incremented after the next fetch
+    halt_bug: bool,
+    /// Low power mode, until a button is pressed
+    is_stopped: bool,
+    /// Interrupt Master Enable
+    ime: bool,
+    /// EI enables the interrupts after the next instruction
+    ime_scheduled: bool,
+    /// An illegal opcode hangs the CPU until reset
+    is_locked: bool,
+    /// T-cycles already ticked on the bus by the current instruction
+    elapsed: Delay,
+    /// Gameboy Doctor trace, one line per instruction
+    trace: Option<Box<dyn Write + Send>>,
     memory: SharedMemory,
 }
 
@@ -26,46 +48,250 @@ impl Cpu {
             pc: 0 as ProgramCounter,
             sp: 0u16,
             is_halted: false,
+            halt_bug: false,
+            is_stopped: false,
+            ime: false,
+            ime_scheduled: false,
+            is_locked: false,
+            elapsed: 0,
+            trace: None,
             memory,
         }
     }
 
+    /// Write the state before each instruction, in the Gameboy Doctor format
+    pub fn set_trace(&mut self, trace: Box<dyn Write + Send>) {
+        self.trace = Some(trace);
+    }
+
+    /// Registers as left by the boot ROM of the model, ready to run the cartridge at 0x0100.
+    /// The DMG boot ROM sets H and C depending on the header checksum.
+    pub fn skip_boot(&mut self, model: Model, header_checksum: u8) {
+        let checksum_flags = if header_checksum != 0 { 0x30 } else { 0x00 };
+        let (af, bc, de, hl) = match model {
+            Model::Dmg0 => (0x0100, 0xFF13, 0x00C1, 0x8403),
+            Model::Dmg => (0x0180 | checksum_flags, 0x0013, 0x00D8, 0x014D),
+            Model::Mgb => (0xFF80 | checksum_flags, 0x0013, 0x00D8, 0x014D),
+            Model::Sgb => (0x0100, 0x0014, 0x0000, 0xC060),
+            Model::Sgb2 => (0xFF00, 0x0014, 0x0000, 0xC060),
+        };
+        self.registers.set_af(af);
+        self.registers.set_bc(bc);
+        self.registers.set_de(de);
+        self.registers.set_hl(hl);
+        self.sp = 0xFFFE;
+        self.pc = 0x0100;
+    }
+
+    /// Run one instruction, or dispatch one interrupt. The bus is ticked one M-cycle at a time
+    /// as the memory accesses occur, the internal cycles left are ticked at the end.
     pub fn step(&mut self) -> Delay {
+        self.elapsed = 0;
+        if self.is_stopped {
+            // The system clock is stopped, nothing on the bus advances
+            self.is_stopped = !self.memory.is_button_pressed();
+            return HALTED_DELAY;
+        }
+        let delay = self.run();
+        debug_assert!(
+            self.elapsed <= delay,
+            "{} T-cycles of bus accesses exceed the {} T-cycles of the step",
+            self.elapsed,
+            delay
+        );
+        self.memory.tick(delay.saturating_sub(self.elapsed));
+        delay
+    }
+
+    fn run(&mut self) -> Delay {
+        if self.is_locked {
+            return LOCKED_DELAY;
+        }
+        if let Some(delay) = self.service_interrupt() {
+            return delay;
+        }
+        if self.is_halted {
+            return HALTED_DELAY;
+        }
+        if self.trace.is_some() {
+            self.write_trace();
+        }
+        // Set by the EI preceding this instruction
+        let enable_interrupts = self.ime_scheduled;
+
         // Check if prefixed instruction
-        let instruction_byte = self.memory.read_byte(self.pc);
-        let instruction = match instruction_byte {
+        let instruction_byte = self.read_cycle(self.pc);
+        let decoded = match instruction_byte {
             // prefetched
-            0xCB => {
-                let instruction_byte = self.memory.read_byte(self.pc + 1);
+            PREFIX => {
+                let instruction_byte = self.read_cycle(self.pc + 1);
                 Instruction::from_prefixed_byte(instruction_byte)
             }
             _ => Instruction::from_byte(instruction_byte),
+        };
+        if self.halt_bug {
+            // The opcode byte is read again as the first operand, or as the next opcode
+            self.halt_bug = false;
+            self.pc = self.pc.wrapping_sub(1);
         }
-        .unwrap_or_else(|| {
-            log::warn!("Unknown instruction : 0x{:x}", instruction_byte);
-            Instruction::Nop
-        });
+        let instruction = match decoded {
+            Some(instruction) => instruction,
+            None => {
+                log::error!(
+                    "Illegal instruction 0x{:02x} at 0x{:04x}, locking the CPU",
+                    instruction_byte,
+                    self.pc
+                );
+                self.is_locked = true;
+                return LOCKED_DELAY;
+            }
+        };
 
         log::trace!(
             "|0x{:2x}|{:24}|Pc:0x{:04x}|HL:0x{:04x}|\r",
             instruction_byte,
-            instruction.to_string(),
+            self.disassemble(),
             self.pc,
             self.registers.hl(),
         );
 
         let (new_pc, delay) = self.execute(instruction);
         self.pc = new_pc;
+        // Unless this instruction is a DI
+        if enable_interrupts && self.ime_scheduled {
+            self.ime = true;
+            self.ime_scheduled = false;
+        }
         delay
     }
 
+    /// `A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02`
+    fn doctor_line(&self) -> String {
+        let registers = &self.registers;
+        let pcmem: Vec<String> = (0..4)
+            .map(|offset| {
+                format!(
+                    "{:02X}",
+                    self.memory.read_byte(self.pc.wrapping_add(offset))
+                )
+            })
+            .collect();
+        format!(
+            "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{}",
+            registers.a(),
+            registers.af() as u8,
+            registers.b(),
+            registers.c(),
+            registers.d(),
+            registers.e(),
+            registers.h(),
+            registers.l(),
+            self.sp,
+            self.pc,
+            pcmem.join(",")
+        )
+    }
+
+    fn write_trace(&mut self) {
+        let line = self.doctor_line();
+        if let Some(trace) = self.trace.as_mut() {
+            if let Err(e) = writeln!(trace, "{}", line) {
+                log::error!("Failed to write the trace, stopping it : {}", e);
+                self.trace = None;
+            }
+        }
+    }
+
+    /// Instruction at PC in RGBDS syntax, for the traces
+    fn disassemble(&self) -> String {
+        let bytes: Vec<u8> = (0..3)
+            .map(|offset| self.memory.read_byte(self.pc.wrapping_add(offset)))
+            .collect();
+        disassembler::disassemble(&bytes, self.pc)
+            .map(|instruction| instruction.to_string())
+            .unwrap_or_default()
+    }
+
+    /// Let the rest of the system run for one M-cycle
+    fn internal_cycle(&mut self) {
+        self.memory.tick(4);
+        self.elapsed += 4;
+    }
+
+    /// Read a byte on the bus, at the end of its M-cycle
+    fn read_cycle(&mut self, address: u16) -> u8 {
+        self.internal_cycle();
+        self.memory.read_byte(address)
+    }
+
+    /// Write a byte on the bus, at the end of its M-cycle
+    fn write_cycle(&mut self, address: u16, value: u8) {
+        self.internal_cycle();
+        self.memory.write_byte(address, value);
+    }
+
+    /// Read a little endian word, one byte per M-cycle
+    fn read_word_cycles(&mut self, address: u16) -> u16 {
+        let lower_part = self.read_cycle(address);
+        let higher_part = self.read_cycle(address.wrapping_add(1));
+        ((higher_part as u16) << 8) | (lower_part as u16)
+    }
+
+    /// Jump to the handler of the pending interrupt with the highest priority.
+    /// Return the delay of the dispatch, if any.
+    fn service_interrupt(&mut self) -> Option<Delay> {
+        let interrupt = self.memory.pending_interrupt()?;
+        // Any pending interrupt wakes up the CPU, even if they are disabled
+        self.is_halted = false;
+        if !self.ime {
+            return None;
+        }
+        log::trace!("Interrupt {:?}", interrupt);
+        self.ime = false;
+        self.memory.acknowledge_interrupt(interrupt);
+        // Two wait states before pushing PC
+        self.internal_cycle();
+        self.internal_cycle();
+        if self.halt_bug {
+            // EI then HALT: the handler returns to the HALT
+            self.halt_bug = false;
+            self.pc = self.pc.wrapping_sub(1);
+        }
+        self.push_word(self.pc);
+        self.pc = interrupt.vector();
+        Some(INTERRUPT_DISPATCH_DELAY)
+    }
+
+    /// Run the instruction. Return the new PC and the T-cycles taken, given by the metadata
+    /// of the instruction depending on whether the branch is taken.
     fn execute(&mut self, instruction: Instruction) -> CpuEffect {
+        let next_pc = self.pc.wrapping_add(instruction.length());
+        let cycles = instruction.cycles();
+        let branch = match &instruction {
+            Instruction::Jump(test, nature) => self.jump(test, nature, next_pc),
+            Instruction::Call(test) => self.call(test, next_pc),
+            Instruction::Ret(test) => self.ret(test),
+            Instruction::Reti => self.reti(),
+            Instruction::Rst(address) => self.rst(*address, next_pc),
+            _ => {
+                self.operate(instruction);
+                None
+            }
+        };
+        match branch {
+            Some(address) => (address, cycles.taken),
+            None => (next_pc, cycles.base),
+        }
+    }
+
+    /// Run an instruction which does not branch
+    fn operate(&mut self, instruction: Instruction) {
         match instruction {
             Instruction::Adc(target) => self.adc(&target),
             Instruction::Add(target) => self.add(&target),
-            // CHECK special treamtment here
-            Instruction::AddHL(_target) => todo!(),
+            Instruction::AddHL(target) => self.add_hl(&target),
             Instruction::AddSp => self.add_sp(),
+            Instruction::LoadHLSp => self.load_hl_sp(),
             Instruction::And(target) => self.and(&target),
             Instruction::Bit(target, byte) => self.bit(&target, byte),
             Instruction::Ccf => self.ccf(),
@@ -93,8 +319,6 @@ impl Cpu {
             Instruction::Sub(target) => self.sub(&target),
             Instruction::Swap(target) => self.swap(&target),
             Instruction::Xor(target) => self.xor(&target),
-            // JUMP
-            Instruction::Jump(test, nature) => self.jump(&test, &nature),
             // TODO check me
             Instruction::Load {
                 to: target,
@@ -104,264 +328,190 @@ impl Cpu {
             // Stack and Call
             Instruction::Push(source) => self.push(&source),
             Instruction::Pop(target) => self.pop(&target),
-            Instruction::Call(test) => self.call(&test),
-            Instruction::Ret(test) => self.ret(&test),
-            Instruction::Reti => self.reti(),
             // Interrupt
             Instruction::DisableInterrupt => self.disable_interrupt(),
             Instruction::EnableInterrupt => self.enable_interrupt(),
             Instruction::Nop => self.nop(),
             Instruction::Halt => self.halt(),
             Instruction::Stop => self.stop(),
-            Instruction::Rst(address) => self.rst(address),
             Instruction::Daa => self.daa(),
             Instruction::Scf => self.scf(),
+            Instruction::Jump(..)
+            | Instruction::Call(_)
+            | Instruction::Ret(_)
+            | Instruction::Reti
+            | Instruction::Rst(_) => unreachable!("Branches are run by execute"),
         }
     }
 
-    fn read_value(&mut self, target: &ArithmeticTarget) -> (u8, ProgramCounter, Delay) {
+    /// Read the target, operands and memory accesses taking one M-cycle each
+    fn read_value(&mut self, target: &ArithmeticTarget) -> u8 {
         match target {
-            ArithmeticTarget::A => (self.registers.a(), 0, 0),
-            ArithmeticTarget::B => (self.registers.b(), 0, 0),
-            ArithmeticTarget::C => (self.registers.c(), 0, 0),
-            ArithmeticTarget::D => (self.registers.d(), 0, 0),
-            ArithmeticTarget::E => (self.registers.e(), 0, 0),
-            ArithmeticTarget::H => (self.registers.h(), 0, 0),
-            ArithmeticTarget::L => (self.registers.l(), 0, 0),
+            ArithmeticTarget::A => self.registers.a(),
+            ArithmeticTarget::B => self.registers.b(),
+            ArithmeticTarget::C => self.registers.c(),
+            ArithmeticTarget::D => self.registers.d(),
+            ArithmeticTarget::E => self.registers.e(),
+            ArithmeticTarget::H => self.registers.h(),
+            ArithmeticTarget::L => self.registers.l(),
             // Memory
-            ArithmeticTarget::BCTarget => (self.memory.read_byte(self.registers.bc()), 0, 4),
-            ArithmeticTarget::DETarget => (self.memory.read_byte(self.registers.de()), 0, 4),
-            ArithmeticTarget::HLTarget => (self.memory.read_byte(self.registers.hl()), 0, 4),
+            ArithmeticTarget::BCTarget => self.read_cycle(self.registers.bc()),
+            ArithmeticTarget::DETarget => self.read_cycle(self.registers.de()),
+            ArithmeticTarget::HLTarget => self.read_cycle(self.registers.hl()),
             // PC
-            ArithmeticTarget::FFC => (
-                self.memory.read_byte(0xFF00 + self.registers.c() as u16),
-                0,
-                4,
-            ),
-            ArithmeticTarget::FFRead => (
-                self.memory
-                    .read_byte(0xFF00 + self.memory.read_byte(self.pc + 1) as u16),
-                1,
-                4,
-            ),
-            ArithmeticTarget::ReadByte => (self.memory.read_byte(self.pc + 1), 1, 4),
-            // CHECKME
+            ArithmeticTarget::FFC => self.read_cycle(0xFF00 + self.registers.c() as u16),
+            ArithmeticTarget::FFRead => {
+                let offset = self.read_cycle(self.pc + 1);
+                self.read_cycle(0xFF00 + offset as u16)
+            }
+            ArithmeticTarget::ReadByte => self.read_cycle(self.pc + 1),
             ArithmeticTarget::Pointer => {
-                let address = self.memory.read_word(self.pc + 1);
-                (self.memory.read_byte(address), 2, 12)
+                let address = self.read_word_cycles(self.pc + 1);
+                self.read_cycle(address)
             }
             // Read value pointer by HL then increment HL
             ArithmeticTarget::HLInc => {
                 let address = self.registers.hl();
-                let value = self.memory.read_byte(address);
-                self.registers.set_hl(address + 1);
-                (value, 1, 8)
+                let value = self.read_cycle(address);
+                self.registers.set_hl(address.wrapping_add(1));
+                value
             }
             // Read value pointer by HL then decrement HL
             ArithmeticTarget::HLDec => {
                 let address = self.registers.hl();
-                let value = self.memory.read_byte(address);
-                self.registers.set_hl(address - 1);
-                (value, 1, 8)
+                let value = self.read_cycle(address);
+                self.registers.set_hl(address.wrapping_sub(1));
+                value
             }
         }
     }
 
-    fn read_value_16(&self, target: &WideArithmeticTarget) -> (u16, ProgramCounter, Delay) {
+    fn read_value_16(&mut self, target: &WideArithmeticTarget) -> u16 {
         match target {
-            WideArithmeticTarget::HL => (self.registers.hl(), 0, 0),
-            WideArithmeticTarget::BC => (self.registers.bc(), 0, 0),
-            WideArithmeticTarget::DE => (self.registers.de(), 0, 0),
-            WideArithmeticTarget::AF => (self.registers.af(), 0, 0),
-            WideArithmeticTarget::SP => (self.memory.read_word(self.sp), 0, 0),
-            WideArithmeticTarget::ReadWord => (self.memory.read_word(self.pc + 1), 2, 4),
+            WideArithmeticTarget::HL => self.registers.hl(),
+            WideArithmeticTarget::BC => self.registers.bc(),
+            WideArithmeticTarget::DE => self.registers.de(),
+            WideArithmeticTarget::AF => self.registers.af(),
+            WideArithmeticTarget::SP => self.sp,
+            WideArithmeticTarget::ReadWord => self.read_word_cycles(self.pc + 1),
             WideArithmeticTarget::ReadAddress => panic!("Reading an address has no value here"),
         }
     }
 
-    /// The the provided value in the right arithmetic target.
-    /// The offset is either 4 or 8
-    fn write_value(&mut self, target: &ArithmeticTarget, value: u8) -> CpuEffect {
+    /// Write the provided value in the right arithmetic target, like `read_value`
+    fn write_value(&mut self, target: &ArithmeticTarget, value: u8) {
         match target {
-            ArithmeticTarget::A => {
-                self.registers.set_a(value);
-                NO_CPU_EFFECT
-            }
-            ArithmeticTarget::B => {
-                self.registers.set_b(value);
-                NO_CPU_EFFECT
-            }
-            ArithmeticTarget::C => {
-                self.registers.set_c(value);
-                NO_CPU_EFFECT
-            }
-            ArithmeticTarget::D => {
-                self.registers.set_d(value);
-                NO_CPU_EFFECT
-            }
-            ArithmeticTarget::E => {
-                self.registers.set_e(value);
-                NO_CPU_EFFECT
-            }
-            ArithmeticTarget::H => {
-                self.registers.set_h(value);
-                NO_CPU_EFFECT
-            }
-            ArithmeticTarget::L => {
-                self.registers.set_l(value);
-                NO_CPU_EFFECT
-            }
+            ArithmeticTarget::A => self.registers.set_a(value),
+            ArithmeticTarget::B => self.registers.set_b(value),
+            ArithmeticTarget::C => self.registers.set_c(value),
+            ArithmeticTarget::D => self.registers.set_d(value),
+            ArithmeticTarget::E => self.registers.set_e(value),
+            ArithmeticTarget::H => self.registers.set_h(value),
+            ArithmeticTarget::L => self.registers.set_l(value),
             // target type
-            ArithmeticTarget::BCTarget => {
-                let address = self.registers.bc();
-                self.memory.write_byte(address, value);
-                (0, 4)
-            }
-            ArithmeticTarget::DETarget => {
-                let address = self.registers.de();
-                self.memory.write_byte(address, value);
-                (0, 4)
-            }
-            ArithmeticTarget::HLTarget => {
-                let address = self.registers.bc();
-                self.memory.write_byte(address, value);
-                (0, 4)
-            }
+            ArithmeticTarget::BCTarget => self.write_cycle(self.registers.bc(), value),
+            ArithmeticTarget::DETarget => self.write_cycle(self.registers.de(), value),
+            ArithmeticTarget::HLTarget => self.write_cycle(self.registers.hl(), value),
             ArithmeticTarget::FFRead => {
-                let offset = self.memory.read_byte(self.pc + 1);
-                let address = 0xFF00 + (offset as u16);
-                self.memory.write_byte(address, value);
-                (1, 4)
-            }
-            ArithmeticTarget::FFC => {
-                let address = 0xFF00 + (self.registers.c() as u16);
-                self.memory.write_byte(address, value);
-                (0, 4)
+                let offset = self.read_cycle(self.pc + 1);
+                self.write_cycle(0xFF00 + offset as u16, value);
             }
+            ArithmeticTarget::FFC => self.write_cycle(0xFF00 + self.registers.c() as u16, value),
             ArithmeticTarget::ReadByte => unreachable!("Can't right directly to next byte."),
             ArithmeticTarget::Pointer => {
-                let address = self.memory.read_word(self.pc + 1);
-                self.memory.write_byte(address, value);
-                (2, 12)
+                let address = self.read_word_cycles(self.pc + 1);
+                self.write_cycle(address, value);
             }
             // SPECIAL
             ArithmeticTarget::HLDec => {
                 let address = self.registers.hl();
-                self.memory.write_byte(address, value);
-                self.registers.set_hl(address - 1);
-                (0, 4)
+                self.write_cycle(address, value);
+                self.registers.set_hl(address.wrapping_sub(1));
             }
             ArithmeticTarget::HLInc => {
                 let address = self.registers.hl();
-                self.memory.write_byte(address, value);
-                self.registers.set_hl(address + 1);
-                (0, 4)
+                self.write_cycle(address, value);
+                self.registers.set_hl(address.wrapping_add(1));
             }
         }
     }
 
-    fn write_value_16(&mut self, target: &WideArithmeticTarget, value: u16) -> Delay {
+    /// Same as `write_value`, for 16 bits targets
+    fn write_value_16(&mut self, target: &WideArithmeticTarget, value: u16) {
         match target {
-            WideArithmeticTarget::HL => {
-                self.registers.set_hl(value);
-                0
-            }
-            WideArithmeticTarget::BC => {
-                self.registers.set_bc(value);
-                0
-            }
-            WideArithmeticTarget::DE => {
-                self.registers.set_de(value);
-                0
-            }
-            WideArithmeticTarget::AF => {
-                self.registers.set_af(value);
-                0
-            }
-            // CHECKME  0 right offset ?
-            WideArithmeticTarget::SP => {
-                self.sp = value;
-                0
-            }
+            WideArithmeticTarget::HL => self.registers.set_hl(value),
+            WideArithmeticTarget::BC => self.registers.set_bc(value),
+            WideArithmeticTarget::DE => self.registers.set_de(value),
+            WideArithmeticTarget::AF => self.registers.set_af(value),
+            WideArithmeticTarget::SP => self.sp = value,
             WideArithmeticTarget::ReadWord => panic!("Can't right directly to the next bytes"),
             WideArithmeticTarget::ReadAddress => {
-                let address = self.memory.read_word(self.pc + 1);
-                self.memory.write_word(address, value);
-                2
+                let address = self.read_word_cycles(self.pc + 1);
+                self.write_cycle(address, (value & 0xFF) as u8);
+                self.write_cycle(address.wrapping_add(1), (value >> 8) as u8);
             }
         }
     }
 
-    fn jump(&self, test: &JumpTest, nature: &JumpType) -> CpuEffect {
-        if test.evaluate(self.registers.f()) {
-            // should jump
-            match nature {
-                JumpType::Relative8 => {
-                    let offset = self.memory.read_byte(self.pc + 1) as i8;
-                    // This comes from the size of the instruction↘️
-                    let address = (self.pc as i32 + offset as i32 + 2) as u16;
-                    (address, 12)
-                }
-                JumpType::Pointer16 => (self.memory.read_word(self.pc + 1), 16),
-                JumpType::HL => (self.registers.hl(), 4),
-                _ => unimplemented!("Jump type missing!"),
+    /// Jump if the condition is met, to the target following the opcode or in HL
+    fn jump(
+        &mut self,
+        test: &JumpTest,
+        nature: &JumpType,
+        next_pc: ProgramCounter,
+    ) -> Option<ProgramCounter> {
+        let address = match nature {
+            JumpType::Relative8 => {
+                let offset = self.read_cycle(self.pc + 1) as i8;
+                next_pc.wrapping_add(offset as u16)
             }
+            JumpType::Pointer16 => self.read_word_cycles(self.pc + 1),
+            JumpType::HL => self.registers.hl(),
+        };
+        if test.evaluate(self.registers.f()) {
+            Some(address)
         } else {
-            // just continue and skip the trailing data
-            match nature {
-                JumpType::Relative8 => (self.pc + 2, 8),
-                JumpType::Pointer16 => (self.pc + 3, 12),
-                JumpType::HL => unreachable!("HL jump as the JumpTest::Always"),
-                _ => unimplemented!("Jump type missing!"),
-            }
+            None
         }
     }
 
-    fn halt(&mut self) -> CpuEffect {
-        self.is_halted = true;
-        (self.pc + 1, 4)
+    /// Sleep until an interrupt is pending, serviced or not depending on IME
+    fn halt(&mut self) {
+        if !self.ime && self.memory.pending_interrupt().is_some() {
+            // HALT bug: the CPU does not sleep
+            self.halt_bug = true;
+        } else {
+            self.is_halted = true;
+        }
     }
 
-    fn load(&mut self, target: &ArithmeticTarget, source: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, source_offset) = self.read_value(source);
+    fn load(&mut self, target: &ArithmeticTarget, source: &ArithmeticTarget) {
+        let value = self.read_value(source);
 
-        let (write_pc_offset, write_offset) = self.write_value(target, value);
-        (
-            self.pc + 1 + pc_offset + write_pc_offset,
-            source_offset + write_offset,
-        )
+        self.write_value(target, value);
     }
 
     /// Load next 2 bytes in memory in the provided registers
-    fn load_16(
-        &mut self,
-        target: &WideArithmeticTarget,
-        source: &WideArithmeticTarget,
-    ) -> CpuEffect {
-        let (value, pc_offset, read_offset) = self.read_value_16(source);
+    fn load_16(&mut self, target: &WideArithmeticTarget, source: &WideArithmeticTarget) {
+        let value = self.read_value_16(source);
 
-        // CHECKME : can we write a u16 in other thing than a register ?
-        let write_offset = self.write_value_16(target, value);
-        (self.pc + 1 + pc_offset, 4 + read_offset + write_offset)
+        self.write_value_16(target, value);
     }
 
     /// no operation
-    fn nop(&mut self) -> CpuEffect {
-        (self.pc + 1, 1)
-    }
+    fn nop(&mut self) {}
 
     /// Complement carry flag
-    fn ccf(&mut self) -> CpuEffect {
+    fn ccf(&mut self) {
         let carry = self.registers.f().carry();
         self.registers.f_as_mut().set_carry(carry.not());
         self.registers.f_as_mut().set_subtract(false);
         self.registers.f_as_mut().set_half_carry(false);
-        (self.pc + 1, 4)
     }
 
     /// Add the content of the targeted register to the A register.
-    fn add(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, offset) = self.read_value(target);
+    fn add(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
         let (new_value, did_overflow) = self.registers.a().overflowing_add(value);
 
         self.registers.f_as_mut().set_zero(new_value == 0);
@@ -373,28 +523,61 @@ impl Cpu {
         self.registers
             .f_as_mut()
             .set_half_carry((register_a & 0xF) + (value & 0xF) > 0xF);
-
-        (self.pc + 1 + pc_offset, offset)
     }
     /// Read the next value as i8 then add it to the SP
-    fn add_sp(&mut self) -> CpuEffect {
-        let (value, _pc_offset, _offset) = self.read_value(&ArithmeticTarget::ReadByte);
-        let sp_value = self.pop_word();
-        self.push_word((sp_value as i16 + value as i16) as u16);
-        (self.pc + 2, 16)
+    fn add_sp(&mut self) {
+        self.sp = self.sp_plus_offset();
+    }
+
+    /// Load SP plus the next value as i8 in HL
+    fn load_hl_sp(&mut self) {
+        let value = self.sp_plus_offset();
+        self.registers.set_hl(value);
+    }
+
+    /// SP plus the next byte as i8.
+    /// The flags are the ones of an unsigned addition on the lower byte.
+    fn sp_plus_offset(&mut self) -> u16 {
+        let offset = self.read_cycle(self.pc + 1);
+        let sp = self.sp;
+
+        self.registers.f_as_mut().set_zero(false);
+        self.registers.f_as_mut().set_subtract(false);
+        self.registers
+            .f_as_mut()
+            .set_half_carry((sp & 0x0F) + (offset as u16 & 0x0F) > 0x0F);
+        self.registers
+            .f_as_mut()
+            .set_carry((sp & 0xFF) + offset as u16 > 0xFF);
+
+        sp.wrapping_add(offset as i8 as u16)
+    }
+
+    /// Add the targeted registers to HL. The zero flag is left untouched.
+    fn add_hl(&mut self, target: &WideArithmeticTarget) {
+        let value = self.read_value_16(target);
+        let hl = self.registers.hl();
+        let (new_value, did_overflow) = hl.overflowing_add(value);
+
+        self.registers.f_as_mut().set_subtract(false);
+        self.registers
+            .f_as_mut()
+            .set_half_carry((hl & 0x0FFF) + (value & 0x0FFF) > 0x0FFF);
+        self.registers.f_as_mut().set_carry(did_overflow);
+
+        self.registers.set_hl(new_value);
     }
 
     /// Add with carry
-    fn adc(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, offset) = self.read_value(target);
+    fn adc(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
+        let carry = if self.registers.f().carry() { 1 } else { 0 };
         // if no overflow, value can overflow
         let (mut new_value, mut did_overflow) = self.registers.a().overflowing_add(value);
 
-        if self.registers.f().carry() {
-            let (new_value_carry, did_overflow_carry) = new_value.overflowing_add(1);
-            new_value = new_value_carry;
-            did_overflow |= did_overflow_carry;
-        }
+        let (new_value_carry, did_overflow_carry) = new_value.overflowing_add(carry);
+        new_value = new_value_carry;
+        did_overflow |= did_overflow_carry;
 
         self.registers.f_as_mut().set_zero(new_value == 0);
         self.registers.f_as_mut().set_subtract(false);
@@ -403,15 +586,13 @@ impl Cpu {
         let register_a = self.registers.a();
         self.registers
             .f_as_mut()
-            .set_half_carry((register_a & 0xF) + (value & 0xF) > 0xF);
+            .set_half_carry((register_a & 0xF) + (value & 0xF) + carry > 0xF);
 
         self.registers.set_a(new_value);
-
-        (self.pc + 1 + pc_offset, 4 + offset)
     }
     /// Subscrate the target value to the A register.
-    fn sub(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, offset) = self.read_value(target);
+    fn sub(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
         let (new_value, did_overflow) = self.registers.a().overflowing_sub(value);
 
         self.registers.f_as_mut().set_zero(new_value == 0);
@@ -424,50 +605,42 @@ impl Cpu {
             .set_half_carry((register_a & 0xF) < (value & 0xF));
 
         self.registers.set_a(new_value);
-
-        (self.pc + 1 + pc_offset, 4 + offset)
     }
 
     /// Like sub but the carry value is also substracted
-    fn sbc(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, offset) = self.read_value(target);
-
-        let (mut new_value, did_overflow) = self.registers.a().overflowing_sub(value);
+    fn sbc(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
+        let carry = if self.registers.f().carry() { 1 } else { 0 };
 
-        if self.registers.f().carry() {
-            new_value = new_value.wrapping_sub(1);
-        }
+        let register_a = self.registers.a();
+        let new_value = register_a.wrapping_sub(value).wrapping_sub(carry);
 
         self.registers.f_as_mut().set_zero(new_value == 0);
         self.registers.f_as_mut().set_subtract(true);
-        self.registers.f_as_mut().set_carry(did_overflow);
-
-        let register_a = self.registers.a();
         self.registers
             .f_as_mut()
-            .set_half_carry((register_a & 0xF) < (value & 0xF));
+            .set_carry((register_a as u16) < value as u16 + carry as u16);
+        self.registers
+            .f_as_mut()
+            .set_half_carry((register_a & 0xF) < (value & 0xF) + carry);
 
         self.registers.set_a(new_value);
-
-        (self.pc + 1 + pc_offset, 4 + offset)
     }
 
-    fn and(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, offset) = self.read_value(target);
+    fn and(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
         let new_value = self.registers.a() & value;
 
         self.registers.f_as_mut().set_zero(new_value == 0);
         self.registers.f_as_mut().set_subtract(false);
-        self.registers.f_as_mut().set_half_carry(false);
+        self.registers.f_as_mut().set_half_carry(true);
         self.registers.f_as_mut().set_carry(false);
 
         self.registers.set_a(new_value);
-
-        (self.pc + 1 + pc_offset, 4 + offset)
     }
 
-    fn xor(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, offset) = self.read_value(target);
+    fn xor(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
         let new_value = self.registers.a() ^ value;
 
         self.registers.f_as_mut().set_zero(new_value == 0);
@@ -476,12 +649,10 @@ impl Cpu {
         self.registers.f_as_mut().set_carry(false);
 
         self.registers.set_a(new_value);
-
-        (self.pc + 1 + pc_offset, 4 + offset)
     }
 
-    fn or(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, offset) = self.read_value(target);
+    fn or(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
         let new_value = self.registers.a() | value;
 
         self.registers.f_as_mut().set_zero(new_value == 0);
@@ -490,11 +661,10 @@ impl Cpu {
         self.registers.f_as_mut().set_carry(false);
 
         self.registers.set_a(new_value);
-        (self.pc + 1 + pc_offset, 4 + offset)
     }
 
-    fn cp(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, offset) = self.read_value(target);
+    fn cp(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
         let (new_value, did_overflow) = self.registers.a().overflowing_sub(value);
 
         self.registers.f_as_mut().set_zero(new_value == 0);
@@ -504,14 +674,12 @@ impl Cpu {
         let register_a = self.registers.a();
         self.registers
             .f_as_mut()
-            .set_half_carry((register_a & 0xF) + (value & 0xF) > 0xF);
-
-        (self.pc + 1 + pc_offset, 4 + offset)
+            .set_half_carry((register_a & 0xF) < (value & 0xF));
     }
 
     /// Shift left arithmetic. Multiplies by 2
-    fn sla(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, source_offset) = self.read_value(target);
+    fn sla(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
         let (new_value, did_overflow) = value.overflowing_mul(2);
 
         self.registers.f_as_mut().set_zero(new_value == 0);
@@ -519,17 +687,12 @@ impl Cpu {
         self.registers.f_as_mut().set_half_carry(false);
         self.registers.f_as_mut().set_carry(did_overflow);
 
-        let (write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
-
-        (
-            self.pc + 1 + pc_offset + write_pc_offset,
-            8 + write_delay_offset + source_offset,
-        )
+        self.write_value(target, new_value);
     }
 
     /// Shift right arithmetic. Divides by 2
-    fn sra(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, read_offset) = self.read_value(target);
+    fn sra(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
         // check first bit
         let carry = (value & 0x01) == 0x01;
         let new_value = (value >> 1) | (value & 0x80);
@@ -539,17 +702,12 @@ impl Cpu {
         self.registers.f_as_mut().set_half_carry(false);
         self.registers.f_as_mut().set_carry(carry);
 
-        let (write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
-
-        (
-            self.pc + 1 + pc_offset + write_pc_offset,
-            4 + read_offset + write_delay_offset,
-        )
+        self.write_value(target, new_value);
     }
 
     /// Bit shift right
-    fn srl(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, read_offset) = self.read_value(target);
+    fn srl(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
         // check first bit
         let carry = (value & 0x01) == 0x01;
 
@@ -560,32 +718,25 @@ impl Cpu {
         self.registers.f_as_mut().set_half_carry(false);
         self.registers.f_as_mut().set_carry(carry);
 
-        let (write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
-
-        (
-            self.pc + 1 + pc_offset + write_pc_offset,
-            4 + read_offset + write_delay_offset,
-        )
+        self.write_value(target, new_value);
     }
 
     /// Rotate right for register A
-    fn rra(&mut self) -> CpuEffect {
+    fn rra(&mut self) {
         let value = self.registers.a();
 
         // check last bit
         let carry = (value & 0x01) == 0x01;
 
-        let new_value = (value >> 1) | (if self.registers.f().carry() { 0x8 } else { 0 });
+        let new_value = (value >> 1) | (if self.registers.f().carry() { 0x80 } else { 0 });
 
-        self.registers.f_as_mut().set_carry(carry);
-        self.registers.f_as_mut().set_zero(new_value == 0);
+        self.set_rotate_a_flags(carry);
 
         self.registers.set_a(new_value);
-        (self.pc + 1, 4)
     }
 
     // Rotate left for register A
-    fn rla(&mut self) -> CpuEffect {
+    fn rla(&mut self) {
         let value = self.registers.a();
 
         // check first bit
@@ -593,15 +744,13 @@ impl Cpu {
 
         let new_value = (value << 1) | (if self.registers.f().carry() { 1 } else { 0 });
 
-        self.registers.f_as_mut().set_carry(carry);
-        self.registers.f_as_mut().set_zero(new_value == 0);
+        self.set_rotate_a_flags(carry);
 
         self.registers.set_a(new_value);
-        (self.pc + 1, 4)
     }
 
     // Rotate right without carry the register A
-    fn rrca(&mut self) -> CpuEffect {
+    fn rrca(&mut self) {
         let value = self.registers.a();
 
         // check first bit
@@ -609,30 +758,35 @@ impl Cpu {
 
         let new_value = (value >> 1) | (if carry { 0x80 } else { 0 });
 
-        self.registers.f_as_mut().set_carry(carry);
-        self.registers.f_as_mut().set_zero(new_value == 0);
+        self.set_rotate_a_flags(carry);
 
         self.registers.set_a(new_value);
-        (self.pc + 1, 4)
     }
     // Rotate left without carry the register A
-    fn rlca(&mut self) -> CpuEffect {
+    fn rlca(&mut self) {
         let value = self.registers.a();
 
         // check first bit
         let carry = (value & 0x80) == 0x80;
 
-        let new_value = (value << 1) | (if self.registers.f().carry() { 1 } else { 0 });
+        let new_value = (value << 1) | (if carry { 1 } else { 0 });
 
-        self.registers.f_as_mut().set_carry(carry);
+        self.set_rotate_a_flags(carry);
 
         self.registers.set_a(new_value);
-        (self.pc + 1, 4)
+    }
+
+    /// Unlike their prefixed versions, rotations of A always clear the zero flag
+    fn set_rotate_a_flags(&mut self, carry: bool) {
+        self.registers.f_as_mut().set_zero(false);
+        self.registers.f_as_mut().set_subtract(false);
+        self.registers.f_as_mut().set_half_carry(false);
+        self.registers.f_as_mut().set_carry(carry);
     }
 
     // rotate left
-    fn rl(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, _pc_offset, read_offset) = self.read_value(target);
+    fn rl(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
 
         // check first bit
         let carry = (value & 0x80) == 0x80;
@@ -644,13 +798,11 @@ impl Cpu {
         self.registers.f_as_mut().set_half_carry(false);
         self.registers.f_as_mut().set_subtract(false);
 
-        let (_write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
-
-        (self.pc + 2, 8 + read_offset + write_delay_offset)
+        self.write_value(target, new_value);
     }
 
-    fn rlc(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, _pc_offset, read_offset) = self.read_value(target);
+    fn rlc(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
 
         // check first bit
         let carry = (value & 0x80) == 0x80;
@@ -662,14 +814,12 @@ impl Cpu {
         self.registers.f_as_mut().set_half_carry(false);
         self.registers.f_as_mut().set_subtract(false);
 
-        let (_write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
-
-        (self.pc + 2, 8 + read_offset + write_delay_offset)
+        self.write_value(target, new_value);
     }
 
     /// Rotate right - rotate via the carry flag by one bit
-    fn rr(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, _pc_offset, read_offset) = self.read_value(target);
+    fn rr(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
 
         // check first bit
         let carry = (value & 0x01) == 0x01;
@@ -680,14 +830,12 @@ impl Cpu {
         self.registers.f_as_mut().set_half_carry(false);
         self.registers.f_as_mut().set_subtract(false);
 
-        let (_write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
-
-        (self.pc + 2, 8 + read_offset + write_delay_offset)
+        self.write_value(target, new_value);
     }
 
     /// Rotate right - rotate NOT via the carry flag by one bit
-    fn rrc(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, _pc_offset, read_offset) = self.read_value(target);
+    fn rrc(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
 
         // check first bit
         let carry = (value & 0x01) == 0x01;
@@ -698,84 +846,53 @@ impl Cpu {
         self.registers.f_as_mut().set_half_carry(false);
         self.registers.f_as_mut().set_subtract(false);
 
-        let (_write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
-
-        (self.pc + 2, 8 + read_offset + write_delay_offset)
+        self.write_value(target, new_value);
     }
 
     /// Increment te value of the specified register by one
-    fn inc(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, read_offset) = self.read_value(target);
+    fn inc(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
         let (new_value, _did_overflow) = value.overflowing_add(1);
 
         self.registers.f_as_mut().set_zero(new_value == 0);
         self.registers.f_as_mut().set_subtract(false);
-        // CHECKME
         self.registers
             .f_as_mut()
-            .set_half_carry((new_value & 0xF) + (value & 0xF) > 0xF);
-
-        let (write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
+            .set_half_carry((value & 0xF) == 0xF);
 
-        (
-            self.pc + 1 + pc_offset + write_pc_offset,
-            4 + read_offset + write_delay_offset,
-        )
+        self.write_value(target, new_value);
     }
 
     /// Increment te value of the specified registers by one
-    fn inc_16(&mut self, target: &WideArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, read_offset) = self.read_value_16(target);
-        let (new_value, _did_overflow) = value.overflowing_add(1);
-
-        self.registers.f_as_mut().set_zero(new_value == 0);
-        self.registers.f_as_mut().set_subtract(false);
-        // CHECKME
-        self.registers
-            .f_as_mut()
-            .set_half_carry((new_value & 0xF) + (value & 0xF) > 0xF);
-
-        let write_delay_offset = self.write_value_16(target, new_value);
+    fn inc_16(&mut self, target: &WideArithmeticTarget) {
+        let value = self.read_value_16(target);
+        // No flag is affected
+        let new_value = value.wrapping_add(1);
 
-        (
-            self.pc + 1 + pc_offset,
-            4 + read_offset + write_delay_offset,
-        )
+        self.write_value_16(target, new_value);
     }
 
-    fn dec(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, read_offset) = self.read_value(target);
+    fn dec(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
         let new_value = value.wrapping_sub(1);
 
         self.registers.f_as_mut().set_zero(new_value == 0);
         self.registers.f_as_mut().set_subtract(true);
         self.registers.f_as_mut().set_half_carry((value & 0xF) == 0);
 
-        let (write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
-
-        (
-            self.pc + 1 + pc_offset + write_pc_offset,
-            4 + read_offset + write_delay_offset,
-        )
+        self.write_value(target, new_value);
     }
 
-    fn dec_16(&mut self, target: &WideArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, read_offset) = self.read_value_16(target);
+    fn dec_16(&mut self, target: &WideArithmeticTarget) {
+        let value = self.read_value_16(target);
+        // No flag is affected
         let new_value = value.wrapping_sub(1);
 
-        self.registers.f_as_mut().set_zero(new_value == 0);
-        self.registers.f_as_mut().set_subtract(true);
-
-        let write_delay_offset = self.write_value_16(target, new_value);
-
-        (
-            self.pc + 1 + pc_offset,
-            4 + read_offset + write_delay_offset,
-        )
+        self.write_value_16(target, new_value);
     }
 
     /// Set the complement to register A
-    fn cpl(&mut self) -> CpuEffect {
+    fn cpl(&mut self) {
         let value = self.registers.a();
         let new_value = value ^ 0xff;
 
@@ -783,69 +900,56 @@ impl Cpu {
         self.registers.f_as_mut().set_half_carry(true);
 
         self.registers.set_a(new_value);
-
-        (self.pc + 1, 4)
     }
 
     /// set register bit at bit position to 1
-    fn set(&mut self, target: &ArithmeticTarget, bit_pos: u8) -> CpuEffect {
-        let (value, _pc_offset, read_offset) = self.read_value(target);
+    fn set(&mut self, target: &ArithmeticTarget, bit_pos: u8) {
+        let value = self.read_value(target);
         let new_value = value | (1u8 << bit_pos);
 
-        let (_write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
-
-        (self.pc + 2, 8 + read_offset + write_delay_offset)
+        self.write_value(target, new_value);
     }
 
     /// reset register bit at bit position to 0
-    fn reset(&mut self, target: &ArithmeticTarget, bit_pos: u8) -> CpuEffect {
-        let (value, _pc_offset, read_offset) = self.read_value(target);
+    fn reset(&mut self, target: &ArithmeticTarget, bit_pos: u8) {
+        let value = self.read_value(target);
         let new_value = value & (!(1 << bit_pos));
 
-        let (_write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
-
-        (self.pc + 2, 8 + write_delay_offset + read_offset)
+        self.write_value(target, new_value);
     }
 
     /// bit value
-    fn bit(&mut self, target: &ArithmeticTarget, bit_pos: u8) -> CpuEffect {
-        let (value, _pc_offset, read_offset) = self.read_value(target);
+    fn bit(&mut self, target: &ArithmeticTarget, bit_pos: u8) {
+        let value = self.read_value(target);
 
         self.registers
             .f_as_mut()
             .set_zero((value & (1 << bit_pos)) == 0);
         self.registers.f_as_mut().set_subtract(false);
         self.registers.f_as_mut().set_half_carry(true);
-
-        (self.pc + 2, 8 + read_offset)
     }
 
     /// swap
     /// CHECKME, swap lower and higher part or swapping all bits?
-    fn swap(&mut self, target: &ArithmeticTarget) -> CpuEffect {
-        let (value, pc_offset, read_offset) = self.read_value(target);
-        let new_value = (value << 4) | (value >> 4);
-        let (write_pc_offset, write_delay_offset) = self.write_value(target, new_value);
+    fn swap(&mut self, target: &ArithmeticTarget) {
+        let value = self.read_value(target);
+        let new_value = value.rotate_right(4);
+        self.write_value(target, new_value);
 
         self.registers.f_as_mut().set_zero(new_value == 0);
         self.registers.f_as_mut().set_subtract(false);
         self.registers.f_as_mut().set_half_carry(false);
         self.registers.f_as_mut().set_carry(false);
-
-        (
-            self.pc + 1 + pc_offset + write_pc_offset,
-            8 + write_delay_offset + read_offset,
-        )
     }
 
     /// Push 2 bytes to stack
-    fn push(&mut self, source: &WideArithmeticTarget) -> CpuEffect {
+    fn push(&mut self, source: &WideArithmeticTarget) {
         // read value from register
-        let (value, _pc_offset, _read_offset) = self.read_value_16(source);
+        let value = self.read_value_16(source);
 
+        // Decrementing SP takes a cycle before the writes
+        self.internal_cycle();
         self.push_word(value);
-
-        (self.pc + 1, 16)
     }
 
     /// Write a word to the stack
@@ -854,93 +958,97 @@ impl Cpu {
         self.sp = self.sp.wrapping_sub(1);
 
         // write most significant part first
-        self.memory.write_byte(self.sp, (value >> 8) as u8);
+        self.write_cycle(self.sp, (value >> 8) as u8);
 
         // decrese stack
         self.sp = self.sp.wrapping_sub(1);
 
         // write least significant part then
-        self.memory.write_byte(self.sp, (value & 0xFF) as u8)
+        self.write_cycle(self.sp, (value & 0xFF) as u8)
     }
 
     /// Pop 2 bytes from the stack
-    fn pop(&mut self, target: &WideArithmeticTarget) -> CpuEffect {
+    fn pop(&mut self, target: &WideArithmeticTarget) {
         let value = self.pop_word();
 
         self.write_value_16(target, value);
-
-        (self.pc + 1, 12)
     }
 
     /// Pop word from the stack and return its value as u16
     fn pop_word(&mut self) -> u16 {
-        let lower_part = self.memory.read_byte(self.sp);
+        let lower_part = self.read_cycle(self.sp);
         self.sp = self.sp.wrapping_add(1);
 
-        let higher_part = self.memory.read_byte(self.sp);
+        let higher_part = self.read_cycle(self.sp);
         self.sp = self.sp.wrapping_add(1);
 
         ((higher_part as u16) << 8) | (lower_part as u16)
     }
 
     /// Call function
-    fn call(&mut self, test: &JumpTest) -> CpuEffect {
-        let next_pc = self.pc + 3;
-
+    fn call(&mut self, test: &JumpTest, next_pc: ProgramCounter) -> Option<ProgramCounter> {
+        // new pc is in the following bytes, read even if the call is not taken
+        let address = self.read_word_cycles(self.pc + 1);
         if test.evaluate(self.registers.f()) {
+            self.internal_cycle();
             self.push_word(next_pc);
-            // new pc is in the following bytes
-            (self.memory.read_word(self.pc + 1), 12)
+            Some(address)
         } else {
-            (next_pc, 12)
+            None
         }
     }
 
     /// Call provided address to reset the process
-    fn rst(&mut self, address: u16) -> CpuEffect {
-        self.push_word(self.pc + 1);
-        (address, 4)
+    fn rst(&mut self, address: u16, next_pc: ProgramCounter) -> Option<ProgramCounter> {
+        self.internal_cycle();
+        self.push_word(next_pc);
+        Some(address)
     }
 
     /// Return from function
-    fn ret(&mut self, test: &JumpTest) -> CpuEffect {
+    fn ret(&mut self, test: &JumpTest) -> Option<ProgramCounter> {
+        if !matches!(test, JumpTest::Always) {
+            // Checking the condition takes a cycle
+            self.internal_cycle();
+        }
         if test.evaluate(self.registers.f()) {
-            let address = self.pop_word();
-
-            // CHEKME
-            return (address, 16);
+            Some(self.pop_word())
+        } else {
+            None
         }
-        (self.pc + 1, 8)
     }
 
     /// Disable the interrupt flag
-    fn disable_interrupt(&mut self) -> CpuEffect {
+    fn disable_interrupt(&mut self) {
         log::info!("Disable interrupt");
-        self.registers.f_as_mut().set_emi(false);
-        (self.pc + 1, 4)
+        self.ime = false;
+        self.ime_scheduled = false;
     }
 
-    fn enable_interrupt(&mut self) -> CpuEffect {
+    fn enable_interrupt(&mut self) {
         log::info!("Enable interrupt");
-        // This flag should be set only *after* the next instruction
-        self.registers.f_as_mut().set_emi(true);
-        (self.pc + 1, 4)
+        // Set only *after* the next instruction
+        self.ime_scheduled = !self.ime;
     }
 
-    fn reti(&mut self) -> CpuEffect {
-        self.registers.f_as_mut().set_emi(true);
+    fn reti(&mut self) -> Option<ProgramCounter> {
+        // No delay, unlike EI
+        self.ime = true;
+        self.ime_scheduled = false;
         self.ret(&JumpTest::Always)
     }
 
     /// Enter CPU very low power mode. Also used to switch between double and normal speed CPU
     /// modes in GBC.
-    fn stop(&mut self) -> CpuEffect {
+    /// The speed switch armed by KEY1 only exists on the CGB, which is not emulated.
+    fn stop(&mut self) {
         log::info!("Stop");
-        (self.pc + 1, 4)
+        self.memory.reset_divider();
+        self.is_stopped = !self.memory.is_button_pressed();
     }
 
     /// Decimal Adjust Accumulator, of the A register
-    fn daa(&mut self) -> CpuEffect {
+    fn daa(&mut self) {
         let mut value = self.registers.a();
         let mut set_carry = false;
         // Create adjust with carries
@@ -974,16 +1082,13 @@ impl Cpu {
         self.registers.f_as_mut().set_carry(set_carry);
 
         self.registers.set_a(value);
-
-        (self.pc + 1, 4)
     }
 
     /// Set Carry Flag
-    fn scf(&mut self) -> CpuEffect {
+    fn scf(&mut self) {
         self.registers.f_as_mut().set_carry(true);
         self.registers.f_as_mut().set_half_carry(false);
         self.registers.f_as_mut().set_subtract(false);
-        (self.pc + 1, 4)
     }
 }
 
@@ -1,5 +1,9 @@
 use crate::gameboy::{
-    arithmetictarget::ArithmeticTarget, instruction::Instruction, memory::MemoryBus,
+    arithmetictarget::{ArithmeticTarget, WideArithmeticTarget},
+    assembler::assemble,
+    instruction::Instruction,
+    memory::MemoryBus,
+    test_memory::read_word,
 };
 use std::ops::Not;
 
@@ -7,6 +11,30 @@ fn create_cpu() -> Cpu {
     Cpu::new(std::sync::Arc::new(MemoryBus::default()))
 }
 
+/// Put the program in work RAM, with the stack at its end
+fn create_cpu_with_program(program: &[u8]) -> Cpu {
+    let mut cpu = create_cpu();
+    for (offset, byte) in program.iter().enumerate() {
+        cpu.memory.write_byte(0xC000 + offset as u16, *byte);
+    }
+    cpu.pc = 0xC000;
+    cpu.sp = 0xDFFE;
+    cpu
+}
+
+/// Assemble the source in work RAM and step through it until it halts
+fn run_program(source: &str) -> Cpu {
+    let program = assemble(source, 0xC000).unwrap_or_else(|error| panic!("{}", error));
+    let mut cpu = create_cpu_with_program(&program);
+    for _ in 0..10_000 {
+        if cpu.is_halted {
+            return cpu;
+        }
+        cpu.step();
+    }
+    panic!("The program never reached HALT")
+}
+
 use super::Cpu;
 mod instructions {
     use super::*;
@@ -79,6 +107,7 @@ mod instructions {
         cpu.execute(instruction);
         assert_eq!(cpu.registers.a(), 0);
         assert!(cpu.registers.f().carry().not());
+        assert!(cpu.registers.f().half_carry());
     }
 
     #[test]
@@ -200,31 +229,131 @@ mod instructions {
     }
 }
 
-#[cfg(NON)]
 mod test_16bits {
+    use super::*;
+
+    /// Operands around the carries out of bits 3, 7, 11 and 15
+    const EDGES: [u16; 12] = [
+        0x0000, 0x0001, 0x000F, 0x0010, 0x00FF, 0x0100, 0x0FFF, 0x1000, 0x7FFF, 0x8000, 0xF0F0,
+        0xFFFF,
+    ];
+
+    /// Reference model: a carry out of a bit shows in the next bit of `a ^ b ^ sum`
+    fn carries(a: u16, b: u16) -> (u16, u32) {
+        let sum = a.wrapping_add(b);
+        let carries = (a as u32 + b as u32) ^ a as u32 ^ b as u32;
+        (sum, carries)
+    }
+
     #[test]
     fn add_hl() {
-        todo!()
+        let mut cpu = create_cpu();
+        for hl in EDGES.iter() {
+            for value in 0..=0xFFFFu16 {
+                let zero = value & 1 == 0;
+                cpu.registers.set_hl(*hl);
+                cpu.registers.set_de(value);
+                cpu.registers.f_as_mut().set_zero(zero);
+                cpu.registers.f_as_mut().set_subtract(true);
+                cpu.execute(Instruction::AddHL(WideArithmeticTarget::DE));
+
+                let (sum, carries) = carries(*hl, value);
+                let context = format!("HL=0x{:04x} DE=0x{:04x}", hl, value);
+                assert_eq!(cpu.registers.hl(), sum, "{}", context);
+                assert_eq!(cpu.registers.f().zero(), zero, "{}", context);
+                assert!(cpu.registers.f().subtract().not(), "{}", context);
+                assert_eq!(
+                    cpu.registers.f().half_carry(),
+                    carries & 0x1000 != 0,
+                    "{}",
+                    context
+                );
+                assert_eq!(
+                    cpu.registers.f().carry(),
+                    carries & 0x1_0000 != 0,
+                    "{}",
+                    context
+                );
+            }
+        }
+
+        // Doubling HL
+        for hl in 0..=0xFFFFu16 {
+            cpu.registers.set_hl(hl);
+            cpu.execute(Instruction::AddHL(WideArithmeticTarget::HL));
+            let (sum, carries) = carries(hl, hl);
+            assert_eq!(cpu.registers.hl(), sum);
+            assert_eq!(cpu.registers.f().half_carry(), carries & 0x1000 != 0);
+            assert_eq!(cpu.registers.f().carry(), carries & 0x1_0000 != 0);
+        }
     }
 
     #[test]
     fn inc() {
-        todo!()
+        let mut cpu = create_cpu();
+        cpu.registers.set_af(0x00F0);
+        for value in 0..=0xFFFFu16 {
+            cpu.registers.set_bc(value);
+            cpu.execute(Instruction::Inc16(WideArithmeticTarget::BC));
+            assert_eq!(cpu.registers.bc(), value.wrapping_add(1));
+            assert_eq!(cpu.registers.af(), 0x00F0);
+        }
     }
 
     #[test]
     fn dec() {
-        todo!()
+        let mut cpu = create_cpu();
+        cpu.registers.set_af(0x0000);
+        for value in 0..=0xFFFFu16 {
+            cpu.sp = value;
+            cpu.execute(Instruction::Dec16(WideArithmeticTarget::SP));
+            assert_eq!(cpu.sp, value.wrapping_sub(1));
+            assert_eq!(cpu.registers.af(), 0x0000);
+        }
+    }
+
+    /// Run ADD SP,e8 or LD HL,SP+e8 for every offset, with every lower byte of SP.
+    /// The flags only depend on the lower bytes, the upper byte of SP still varies.
+    fn check_sp_plus_offset(instruction: fn() -> Instruction, result: fn(&Cpu) -> u16) {
+        for offset in 0..=0xFFu8 {
+            let mut cpu = create_cpu_with_program(&[0x00, offset]);
+            for lower in 0..=0xFFu16 {
+                let sp = (lower << 8 | lower) ^ 0x8000;
+                cpu.pc = 0xC000;
+                cpu.sp = sp;
+                cpu.registers.set_af(0x00F0);
+                let (pc, _delay) = cpu.execute(instruction());
+
+                let (sum, carries) = carries(sp, offset as i8 as u16);
+                let context = format!("SP=0x{:04x} e8={}", sp, offset as i8);
+                assert_eq!(pc, 0xC002, "{}", context);
+                assert_eq!(result(&cpu), sum, "{}", context);
+                assert!(cpu.registers.f().zero().not(), "{}", context);
+                assert!(cpu.registers.f().subtract().not(), "{}", context);
+                assert_eq!(
+                    cpu.registers.f().half_carry(),
+                    carries & 0x10 != 0,
+                    "{}",
+                    context
+                );
+                assert_eq!(
+                    cpu.registers.f().carry(),
+                    carries & 0x100 != 0,
+                    "{}",
+                    context
+                );
+            }
+        }
     }
 
     #[test]
     fn add_sp() {
-        todo!()
+        check_sp_plus_offset(|| Instruction::AddSp, |cpu| cpu.sp);
     }
 
     #[test]
     fn ld() {
-        todo!()
+        check_sp_plus_offset(|| Instruction::LoadHLSp, |cpu| cpu.registers.hl());
     }
 }
 
@@ -258,7 +387,8 @@ mod test_rotate_shift {
         cpu.execute(instruction);
         assert_eq!(cpu.registers.a(), 0b0000_0000);
         assert!(cpu.registers.f().carry());
-        assert!(cpu.registers.f().zero());
+        // Unlike RL A
+        assert!(cpu.registers.f().zero().not());
     }
 
     #[test]
@@ -499,98 +629,1048 @@ mod test_1bit {
     }
 }
 
-#[cfg(NON)]
 mod test_cpu_control {
+    use super::*;
+
     #[test]
     fn ccf() {
-        todo!()
+        let cpu = run_program("scf\nccf\nhalt");
+        assert!(cpu.registers.f().carry().not());
+        let cpu = run_program("and a\nccf\nhalt");
+        assert!(cpu.registers.f().carry());
+        assert!(cpu.registers.f().half_carry().not());
     }
 
     #[test]
     fn scf() {
-        todo!()
+        let cpu = run_program("ld a, $0F\ninc a\nscf\nhalt");
+        assert!(cpu.registers.f().carry());
+        assert!(cpu.registers.f().half_carry().not());
+        assert!(cpu.registers.f().subtract().not());
     }
 
     #[test]
     fn nop() {
-        todo!()
+        let cpu = run_program("nop\nnop\nhalt");
+        assert_eq!(cpu.pc, 0xC003);
+        assert_eq!(cpu.registers.a(), 0);
     }
 
     #[test]
     fn halt() {
-        todo!()
+        let mut cpu = run_program("halt\nld a, 1");
+        assert_eq!(cpu.pc, 0xC001);
+        cpu.step();
+        assert_eq!(cpu.registers.a(), 0);
     }
 
     #[test]
     fn stop() {
-        todo!()
+        let mut cpu = create_cpu_with_program(&assemble("stop\nld a, 1", 0xC000).unwrap());
+        cpu.step();
+        assert!(cpu.is_stopped);
+        assert_eq!(cpu.pc, 0xC002);
     }
 
     #[test]
     fn di() {
-        todo!()
+        let cpu = run_program("ei\nnop\ndi\nhalt");
+        assert!(cpu.ime.not());
     }
 
     #[test]
     fn ei() {
-        todo!()
+        let cpu = run_program("ei\nnop\nhalt");
+        assert!(cpu.ime);
     }
 }
 
-#[cfg(NON)]
 mod test_jump {
+    use super::*;
+
     #[test]
     fn jp_nn() {
-        todo!()
+        let cpu = run_program(
+            "
+                jp skip
+                ld a, 1
+            skip:
+                halt
+            ",
+        );
+        assert_eq!(cpu.registers.a(), 0);
     }
 
     #[test]
     fn jp_hl() {
-        todo!()
+        let cpu = run_program(
+            "
+                ld hl, skip
+                jp hl
+                ld a, 1
+            skip:
+                halt
+            ",
+        );
+        assert_eq!(cpu.registers.a(), 0);
     }
 
     #[test]
     fn jp_conditionnal() {
-        todo!()
+        let cpu = run_program(
+            "
+                xor a
+                jp nz, not_taken
+                jp z, taken
+            not_taken:
+                ld b, 1
+            taken:
+                scf
+                jp nc, not_taken
+                halt
+            ",
+        );
+        assert_eq!(cpu.registers.b(), 0);
     }
 
     #[test]
     fn jp_relative() {
-        todo!()
+        let cpu = run_program(
+            "
+                jr forward
+            backward:
+                halt
+            forward:
+                ld a, 1
+                jr backward
+            ",
+        );
+        assert_eq!(cpu.registers.a(), 1);
+        assert_eq!(cpu.pc, 0xC003);
     }
 
     #[test]
     fn jp_relative_conditionnal() {
-        todo!()
+        let cpu = run_program(
+            "
+                ld b, 5
+            .loop:
+                inc a
+                dec b
+                jr nz, .loop
+                halt
+            ",
+        );
+        assert_eq!(cpu.registers.a(), 5);
+        assert_eq!(cpu.registers.b(), 0);
     }
 
     #[test]
     fn call_nn() {
-        todo!()
+        let cpu = run_program(
+            "
+                call function
+                halt
+            function:
+                ld a, 1
+                halt
+            ",
+        );
+        assert_eq!(cpu.registers.a(), 1);
+        assert_eq!(cpu.sp, 0xDFFC);
+        assert_eq!(read_word(&cpu.memory, 0xDFFC), 0xC003);
     }
 
     #[test]
     fn call_conditionnal() {
-        todo!()
+        let cpu = run_program(
+            "
+                scf
+                call nc, function
+                call c, function
+                halt
+            function:
+                inc a
+                ret
+            ",
+        );
+        assert_eq!(cpu.registers.a(), 1);
+        assert_eq!(cpu.sp, 0xDFFE);
     }
 
     #[test]
     fn ret() {
-        todo!()
+        let cpu = run_program(
+            "
+                ld hl, back
+                push hl
+                ret
+                ld a, 1
+            back:
+                halt
+            ",
+        );
+        assert_eq!(cpu.registers.a(), 0);
+        assert_eq!(cpu.sp, 0xDFFE);
     }
 
     #[test]
     fn ret_conditionnal() {
-        todo!()
+        let cpu = run_program(
+            "
+                call function
+                halt
+            function:
+                xor a
+                ret nz
+                inc a
+                ret nz
+                ld a, $FF
+                ret
+            ",
+        );
+        assert_eq!(cpu.registers.a(), 1);
+        assert_eq!(cpu.sp, 0xDFFE);
     }
 
     #[test]
     fn ret_interrupt() {
-        todo!()
+        let cpu = run_program(
+            "
+                call function
+                halt
+            function:
+                reti
+            ",
+        );
+        assert!(cpu.ime);
+        assert_eq!(cpu.pc, 0xC004);
     }
 
     #[test]
     fn rst() {
-        todo!()
+        let mut cpu = create_cpu_with_program(&assemble("nop\nrst $38", 0xC000).unwrap());
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.pc, 0x0038);
+        assert_eq!(read_word(&cpu.memory, cpu.sp), 0xC002);
+    }
+}
+
+mod test_trace {
+    use super::*;
+    use crate::gameboy::test_output::SharedOutput;
+
+    #[test]
+    fn doctor_format() {
+        let mut cpu = create_cpu_with_program(&[0x00, 0xC3, 0x13, 0x02]);
+        cpu.skip_boot(crate::gameboy::Model::Dmg, 0x3B);
+        cpu.pc = 0xC000;
+        assert_eq!(
+            cpu.doctor_line(),
+            "A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:C000 PCMEM:00,C3,13,02"
+        );
+    }
+
+    #[test]
+    fn one_line_per_instruction() {
+        let program = assemble("ld a, $12\ninc b\nhalt", 0xC000).unwrap();
+        let mut cpu = create_cpu_with_program(&program);
+        let trace = SharedOutput::default();
+        cpu.set_trace(Box::new(trace.clone()));
+        while !cpu.is_halted {
+            cpu.step();
+        }
+        // Halted, nothing is run
+        cpu.step();
+        let text = trace.text();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("A:00 F:00 B:00"));
+        assert!(lines[0].ends_with("SP:DFFE PC:C000 PCMEM:3E,12,04,76"));
+        assert!(lines[1].starts_with("A:12 F:00 B:00"));
+        assert!(lines[1].ends_with("PC:C002 PCMEM:04,76,00,00"));
+        assert!(lines[2].starts_with("A:12 F:00 B:01"));
+        assert!(lines[2].ends_with("PC:C003 PCMEM:76,00,00,00"));
+    }
+
+    #[test]
+    fn ly_reads_doctor_value() {
+        use crate::gameboy::{Gameboy, LoadOptions};
+
+        let path = std::env::temp_dir().join(format!("gb-trace-{}.log", std::process::id()));
+        let options = LoadOptions {
+            trace: Some(path.clone()),
+            ..LoadOptions::default()
+        };
+        let mut gameboy = Gameboy::new(&options).unwrap();
+        gameboy.bus.set_ly(0x12);
+        let program = assemble("ldh a, [$FF44]", 0xC000).unwrap();
+        for (offset, byte) in program.iter().enumerate() {
+            gameboy.bus.write_byte(0xC000 + offset as u16, *byte);
+        }
+        gameboy.cpu.pc = 0xC000;
+        gameboy.cpu.step();
+        assert_eq!(gameboy.cpu.registers.a(), 0x90);
+        // The trace file is closed with the CPU
+        drop(gameboy);
+
+        let trace = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(trace.ends_with("PC:C000 PCMEM:F0,44,00,00\n"), "{}", trace);
+    }
+}
+
+mod test_blargg {
+    use super::*;
+    use crate::gameboy::test_output::SharedOutput;
+    use crate::gameboy::{cartridge::Cartridge, Model};
+    use std::sync::Arc;
+
+    /// Emulated time after which the ROM is considered stuck
+    const TIMEOUT_CYCLES: u64 = 4_194_304 * 120;
+
+    /// The results are sent over the serial port
+    #[test]
+    #[ignore = "takes minutes without optimizations, run with --release -- --ignored"]
+    fn cpu_instrs() {
+        let cartridge = Cartridge::from_file("test/cpu_instrs.gb").unwrap();
+        let memory = Arc::new(MemoryBus::load(&cartridge));
+        memory.skip_boot(Model::Dmg);
+        let output = SharedOutput::default();
+        memory.set_serial_sink(Box::new(output.clone()));
+        let mut cpu = Cpu::new(memory);
+        cpu.skip_boot(Model::Dmg, cartridge.header().header_checksum);
+
+        let mut cycles = 0u64;
+        let mut received = 0;
+        while cycles < TIMEOUT_CYCLES {
+            cycles += cpu.step() as u64;
+            if output.len() == received {
+                continue;
+            }
+            let text = output.text();
+            if text.contains("Passed all tests") || text.contains("Failed") {
+                break;
+            }
+            received = text.len();
+        }
+        let text = output.text();
+        assert!(text.contains("Passed all tests"), "{}", text);
+    }
+}
+
+mod test_boot {
+    use super::*;
+    use crate::gameboy::Model;
+
+    #[test]
+    fn skip_boot_dmg() {
+        let mut cpu = create_cpu();
+        cpu.skip_boot(Model::Dmg, 0x3B);
+        assert_eq!(cpu.registers.af(), 0x01B0);
+        assert_eq!(cpu.registers.bc(), 0x0013);
+        assert_eq!(cpu.registers.de(), 0x00D8);
+        assert_eq!(cpu.registers.hl(), 0x014D);
+        assert_eq!(cpu.sp, 0xFFFE);
+        assert_eq!(cpu.pc, 0x0100);
+
+        // H and C are cleared by a null header checksum
+        cpu.skip_boot(Model::Dmg, 0x00);
+        assert_eq!(cpu.registers.af(), 0x0180);
+    }
+
+    #[test]
+    fn skip_boot_sgb() {
+        let mut cpu = create_cpu();
+        cpu.skip_boot(Model::Sgb2, 0x3B);
+        assert_eq!(cpu.registers.af(), 0xFF00);
+        assert_eq!(cpu.registers.hl(), 0xC060);
+    }
+}
+
+mod test_interrupts {
+    use super::*;
+    use crate::gameboy::memory::{Button, Interrupt};
+
+    /// Put the program in work RAM, interrupts enabled in IE
+    fn create_cpu_with_program(program: &[u8]) -> Cpu {
+        let cpu = super::create_cpu_with_program(program);
+        cpu.memory.write_byte(0xFFFF, 0x1F);
+        cpu
+    }
+
+    #[test]
+    fn dispatch() {
+        let mut cpu = create_cpu_with_program(&[0x00]);
+        cpu.ime = true;
+        cpu.memory.write_byte(0xFFFF, 0x05);
+        cpu.memory.request_interrupt(Interrupt::Timer);
+        cpu.memory.request_interrupt(Interrupt::Serial);
+        cpu.memory.request_interrupt(Interrupt::VBlank);
+
+        // VBlank has the highest priority
+        assert_eq!(cpu.step(), 20);
+        assert_eq!(cpu.pc, 0x0040);
+        assert_eq!(cpu.sp, 0xDFFC);
+        assert_eq!(read_word(&cpu.memory, 0xDFFC), 0xC000);
+        assert!(cpu.ime.not());
+        assert_eq!(cpu.memory.read_byte(0xFF0F), 0xEC);
+
+        // Disabled by IME
+        cpu.step();
+        assert_eq!(cpu.pc, 0x0041);
+
+        // Then Timer, Serial is not enabled in IE
+        cpu.ime = true;
+        cpu.step();
+        assert_eq!(cpu.pc, 0x0050);
+        assert_eq!(cpu.memory.read_byte(0xFF0F), 0xE8);
+    }
+
+    #[test]
+    fn ei_delay() {
+        // EI, NOP, NOP
+        let mut cpu = create_cpu_with_program(&[0xFB, 0x00, 0x00]);
+        cpu.memory.request_interrupt(Interrupt::Joypad);
+        cpu.step();
+        assert!(cpu.ime.not());
+        // The instruction following EI is executed
+        cpu.step();
+        assert_eq!(cpu.pc, 0xC002);
+        assert!(cpu.ime);
+        cpu.step();
+        assert_eq!(cpu.pc, 0x0060);
+        assert_eq!(read_word(&cpu.memory, cpu.sp), 0xC002);
+    }
+
+    #[test]
+    fn di_cancels_ei() {
+        // EI, DI, NOP
+        let mut cpu = create_cpu_with_program(&[0xFB, 0xF3, 0x00]);
+        cpu.memory.request_interrupt(Interrupt::Stat);
+        cpu.step();
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.pc, 0xC003);
+        assert!(cpu.ime.not());
+    }
+
+    #[test]
+    fn reti_enables_immediately() {
+        // RETI
+        let mut cpu = create_cpu_with_program(&[0xD9]);
+        cpu.push_word(0x1234);
+        cpu.memory.request_interrupt(Interrupt::Stat);
+        cpu.step();
+        assert_eq!(cpu.pc, 0x1234);
+        assert!(cpu.ime);
+        cpu.step();
+        assert_eq!(cpu.pc, 0x0048);
+    }
+
+    #[test]
+    fn flags_do_not_hold_ime() {
+        let mut cpu = create_cpu_with_program(&[0xFB, 0x00]);
+        cpu.step();
+        cpu.step();
+        assert!(cpu.ime);
+        cpu.registers.set_af(0x00FF);
+        assert_eq!(cpu.registers.af(), 0x00F0);
+        assert!(cpu.ime);
+    }
+
+    #[test]
+    fn pending_interrupt_wakes_up() {
+        let mut cpu = create_cpu_with_program(&[0x00]);
+        cpu.is_halted = true;
+        cpu.memory.request_interrupt(Interrupt::VBlank);
+        cpu.step();
+        assert!(cpu.is_halted.not());
+        assert_eq!(cpu.pc, 0xC001);
+    }
+
+    #[test]
+    fn halt_waits_for_interrupt() {
+        // HALT, INC A
+        let mut cpu = create_cpu_with_program(&[0x76, 0x3C]);
+        cpu.step();
+        assert!(cpu.is_halted);
+        for _ in 0..10 {
+            assert_eq!(cpu.step(), 4);
+        }
+        assert_eq!(cpu.pc, 0xC001);
+
+        // Without IME, the execution resumes after the HALT
+        cpu.memory.request_interrupt(Interrupt::Timer);
+        cpu.step();
+        assert!(cpu.is_halted.not());
+        assert_eq!(cpu.pc, 0xC002);
+        assert_eq!(cpu.registers.a(), 0x01);
+        assert_eq!(cpu.memory.read_byte(0xFF0F), 0xE4);
+    }
+
+    #[test]
+    fn halt_services_interrupt() {
+        let mut cpu = create_cpu_with_program(&[0x76, 0x3C]);
+        cpu.ime = true;
+        cpu.step();
+        cpu.step();
+        assert!(cpu.is_halted);
+
+        cpu.memory.request_interrupt(Interrupt::Timer);
+        assert_eq!(cpu.step(), 20);
+        assert!(cpu.is_halted.not());
+        assert_eq!(cpu.pc, 0x0050);
+        assert_eq!(cpu.memory.read_byte(0xDFFC), 0x01);
+    }
+
+    #[test]
+    fn halt_bug() {
+        // HALT, LD A,0x14: the opcode of LD is read twice
+        let mut cpu = create_cpu_with_program(&[0x76, 0x3E, 0x14]);
+        cpu.memory.request_interrupt(Interrupt::Timer);
+        cpu.step();
+        assert!(cpu.is_halted.not());
+        cpu.step();
+        assert_eq!(cpu.registers.a(), 0x3E);
+        assert_eq!(cpu.pc, 0xC002);
+
+        // HALT, INC A: INC is executed twice
+        let mut cpu = create_cpu_with_program(&[0x76, 0x3C, 0x00]);
+        cpu.memory.request_interrupt(Interrupt::Timer);
+        cpu.step();
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.registers.a(), 0x02);
+        assert_eq!(cpu.pc, 0xC002);
+    }
+
+    #[test]
+    fn halt_bug_after_ei() {
+        // EI, HALT: the handler returns to the HALT
+        let mut cpu = create_cpu_with_program(&[0xFB, 0x76, 0x00]);
+        cpu.memory.request_interrupt(Interrupt::Timer);
+        cpu.step();
+        cpu.step();
+        assert!(cpu.is_halted.not());
+        assert_eq!(cpu.step(), 20);
+        assert_eq!(cpu.pc, 0x0050);
+        assert_eq!(cpu.memory.read_byte(0xDFFC), 0x01);
+        assert!(cpu.halt_bug.not());
+    }
+
+    #[test]
+    fn stop() {
+        let mut cpu = create_cpu_with_program(&[0x10, 0x00, 0x3C]);
+        cpu.memory.write_byte(0xFF07, 0x05);
+        cpu.memory.tick(0x1234);
+        cpu.step();
+        assert!(cpu.is_stopped);
+        assert_eq!(cpu.memory.read_byte(0xFF04), 0x00);
+        let tima = cpu.memory.read_byte(0xFF05);
+
+        // Nothing runs until a button is pressed, not even the timer
+        for _ in 0..100 {
+            cpu.step();
+        }
+        assert_eq!(cpu.pc, 0xC002);
+        assert_eq!(cpu.memory.read_byte(0xFF05), tima);
+        assert_eq!(cpu.registers.a(), 0x00);
+    }
+
+    #[test]
+    fn button_wakes_stop() {
+        let mut cpu = create_cpu_with_program(&[0x10, 0x00, 0x3C]);
+        // Action buttons selected
+        cpu.memory.write_byte(0xFF00, 0x10);
+        cpu.step();
+        assert!(cpu.is_stopped);
+        cpu.step();
+        assert!(cpu.is_stopped);
+
+        // Directions are not selected
+        cpu.memory.set_button(Button::Up, true);
+        cpu.step();
+        assert!(cpu.is_stopped);
+
+        cpu.memory.set_button(Button::Start, true);
+        cpu.step();
+        assert!(cpu.is_stopped.not());
+        cpu.step();
+        assert_eq!(cpu.registers.a(), 0x01);
+        assert_eq!(cpu.pc, 0xC003);
+    }
+
+    #[test]
+    fn stop_with_button_held() {
+        let mut cpu = create_cpu_with_program(&[0x10, 0x00, 0x3C]);
+        cpu.memory.write_byte(0xFF00, 0x20);
+        cpu.memory.set_button(Button::Down, true);
+        cpu.step();
+        assert!(cpu.is_stopped.not());
+        cpu.step();
+        assert_eq!(cpu.registers.a(), 0x01);
+    }
+}
+
+mod test_opcodes {
+    use super::*;
+
+    const ILLEGAL_OPCODES: [u8; 11] = [
+        0xD3, 0xDB, 0xDD, 0xE3, 0xE4, 0xEB, 0xEC, 0xED, 0xF4, 0xFC, 0xFD,
+    ];
+
+    #[test]
+    fn decode_all() {
+        for byte in 0..=0xFFu8 {
+            let decoded = Instruction::from_byte(byte);
+            if byte == 0xCB || ILLEGAL_OPCODES.contains(&byte) {
+                assert!(decoded.is_none(), "0x{:02x}", byte);
+            } else {
+                assert!(decoded.is_some(), "0x{:02x}", byte);
+            }
+            assert!(Instruction::from_prefixed_byte(byte).is_some());
+        }
+    }
+
+    #[test]
+    fn execute_all() {
+        // Every legal opcode runs with its operands, without panicking
+        for byte in 0..=0xFFu8 {
+            if ILLEGAL_OPCODES.contains(&byte) {
+                continue;
+            }
+            let mut cpu = create_cpu_with_program(&[byte, 0x01, 0xC0]);
+            cpu.registers.set_hl(0xC100);
+            cpu.step();
+            assert!(cpu.is_locked.not(), "0x{:02x}", byte);
+
+            let mut cpu = create_cpu_with_program(&[0xCB, byte]);
+            cpu.registers.set_hl(0xC100);
+            cpu.step();
+            assert_eq!(cpu.pc, 0xC002, "0xcb 0x{:02x}", byte);
+        }
+    }
+
+    #[test]
+    fn illegal_opcode_locks() {
+        for &byte in ILLEGAL_OPCODES.iter() {
+            let mut cpu = create_cpu_with_program(&[byte, 0x00]);
+            cpu.ime = true;
+            cpu.memory.write_byte(0xFFFF, 0x1F);
+            cpu.step();
+            assert!(cpu.is_locked);
+            // Neither instructions nor interrupts run anymore
+            cpu.memory.write_byte(0xFF0F, 0x1F);
+            cpu.step();
+            cpu.step();
+            assert_eq!(cpu.pc, 0xC000);
+        }
+    }
+
+    #[test]
+    fn instruction_lengths() {
+        // LD A,(HL+)
+        let mut cpu = create_cpu_with_program(&[0x2A]);
+        cpu.registers.set_hl(0xC000);
+        cpu.step();
+        assert_eq!(cpu.pc, 0xC001);
+        assert_eq!(cpu.registers.a(), 0x2A);
+        assert_eq!(cpu.registers.hl(), 0xC001);
+
+        // LD (a16),SP
+        let mut cpu = create_cpu_with_program(&[0x08, 0x00, 0xC1]);
+        cpu.step();
+        assert_eq!(cpu.pc, 0xC003);
+        assert_eq!(read_word(&cpu.memory, 0xC100), 0xDFFE);
+
+        // STOP is followed by a byte
+        let mut cpu = create_cpu_with_program(&[0x10, 0x00]);
+        cpu.step();
+        assert_eq!(cpu.pc, 0xC002);
+    }
+
+    #[test]
+    fn load_hl_target() {
+        // LD (HL),n then LD C,(HL)
+        let mut cpu = create_cpu_with_program(&[0x36, 0x42, 0x4E]);
+        cpu.registers.set_bc(0xC200);
+        cpu.registers.set_hl(0xC100);
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.memory.read_byte(0xC100), 0x42);
+        assert_eq!(cpu.memory.read_byte(0xC200), 0x00);
+        assert_eq!(cpu.registers.c(), 0x42);
+    }
+
+    #[test]
+    fn wide_inc_dec_keep_flags() {
+        // INC SP, DEC HL
+        let mut cpu = create_cpu_with_program(&[0x33, 0x2B]);
+        cpu.registers.set_af(0x00B0);
+        cpu.registers.set_hl(0x0000);
+        cpu.step();
+        cpu.step();
+        assert_eq!(cpu.sp, 0xDFFF);
+        assert_eq!(cpu.registers.hl(), 0xFFFF);
+        assert_eq!(cpu.registers.af(), 0x00B0);
+    }
+
+    #[test]
+    fn add_hl() {
+        let mut cpu = create_cpu();
+        cpu.registers.set_hl(0x0FFF);
+        cpu.registers.f_as_mut().set_zero(true);
+        cpu.execute(Instruction::AddHL(WideArithmeticTarget::HL));
+        assert_eq!(cpu.registers.hl(), 0x1FFE);
+        assert!(cpu.registers.f().half_carry());
+        assert!(cpu.registers.f().carry().not());
+        assert!(cpu.registers.f().zero());
+    }
+
+    #[test]
+    fn sp_offset() {
+        // ADD SP,-1 then LD HL,SP+1
+        let mut cpu = create_cpu_with_program(&[0xE8, 0xFF, 0xF8, 0x01]);
+        cpu.step();
+        assert_eq!(cpu.sp, 0xDFFD);
+        assert!(cpu.registers.f().carry());
+        assert!(cpu.registers.f().half_carry());
+        assert_eq!(read_word(&cpu.memory, 0xDFFC), 0x0000);
+        cpu.step();
+        assert_eq!(cpu.registers.hl(), 0xDFFE);
+        assert_eq!(cpu.sp, 0xDFFD);
+    }
+
+    #[test]
+    fn carry_in() {
+        let mut cpu = create_cpu();
+        cpu.registers.set_a(0x0F);
+        cpu.registers.set_b(0x00);
+        cpu.registers.f_as_mut().set_carry(true);
+        cpu.execute(Instruction::Adc(ArithmeticTarget::B));
+        assert_eq!(cpu.registers.a(), 0x10);
+        assert!(cpu.registers.f().half_carry());
+
+        cpu.registers.set_a(0x10);
+        cpu.registers.f_as_mut().set_carry(true);
+        cpu.execute(Instruction::Sbc(ArithmeticTarget::B));
+        assert_eq!(cpu.registers.a(), 0x0F);
+        assert!(cpu.registers.f().half_carry());
+        assert!(cpu.registers.f().carry().not());
+
+        cpu.registers.set_a(0x00);
+        cpu.registers.set_b(0xFF);
+        cpu.registers.f_as_mut().set_carry(true);
+        cpu.execute(Instruction::Sbc(ArithmeticTarget::B));
+        assert_eq!(cpu.registers.a(), 0x00);
+        assert!(cpu.registers.f().carry());
+        assert!(cpu.registers.f().zero());
+    }
+
+    #[test]
+    fn cp_half_carry() {
+        let mut cpu = create_cpu();
+        cpu.registers.set_a(0x10);
+        cpu.registers.set_b(0x01);
+        cpu.execute(Instruction::Cp(ArithmeticTarget::B));
+        assert!(cpu.registers.f().half_carry());
+        cpu.registers.set_a(0x11);
+        cpu.execute(Instruction::Cp(ArithmeticTarget::B));
+        assert!(cpu.registers.f().half_carry().not());
+    }
+
+    #[test]
+    fn rotate_a() {
+        let mut cpu = create_cpu();
+        cpu.registers.set_a(0b1000_0000);
+        cpu.execute(Instruction::Rlca);
+        assert_eq!(cpu.registers.a(), 0b0000_0001);
+        assert!(cpu.registers.f().carry());
+
+        cpu.registers.set_a(0b0000_0000);
+        cpu.execute(Instruction::Rra);
+        assert_eq!(cpu.registers.a(), 0b1000_0000);
+        assert!(cpu.registers.f().carry().not());
+        assert!(cpu.registers.f().zero().not());
+    }
+}
+
+mod test_metadata {
+    use super::*;
+    use crate::gameboy::instruction::{FlagEffect, Immediate, PREFIX};
+
+    /// Bytes of each unprefixed opcode, 0 for the prefix and the illegal ones
+    #[rustfmt::skip]
+    const OPCODE_LENGTHS: [u16; 256] = [
+        1, 3, 1, 1, 1, 1, 2, 1, 3, 1, 1, 1, 1, 1, 2, 1, // 0x
+        2, 3, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 1, 1, 2, 1, // 1x
+        2, 3, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 1, 1, 2, 1, // 2x
+        2, 3, 1, 1, 1, 1, 2, 1, 2, 1, 1, 1, 1, 1, 2, 1, // 3x
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, // 4x
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, // 5x
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, // 6x
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, // 7x
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, // 8x
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, // 9x
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, // Ax
+        1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, // Bx
+        1, 1, 3, 3, 3, 1, 2, 1, 1, 1, 3, 0, 3, 3, 2, 1, // Cx
+        1, 1, 3, 0, 3, 1, 2, 1, 1, 1, 3, 0, 3, 0, 2, 1, // Dx
+        2, 1, 1, 0, 0, 1, 2, 1, 2, 1, 3, 0, 0, 0, 2, 1, // Ex
+        2, 1, 1, 1, 0, 1, 2, 1, 2, 1, 3, 1, 0, 0, 2, 1, // Fx
+    ];
+
+    #[test]
+    fn lengths() {
+        for opcode in 0..=0xFFu8 {
+            let length = Instruction::from_byte(opcode).map_or(0, |i| i.length());
+            assert_eq!(length, OPCODE_LENGTHS[opcode as usize], "0x{:02x}", opcode);
+
+            let instruction = Instruction::from_prefixed_byte(opcode).unwrap();
+            assert!(instruction.is_prefixed());
+            assert_eq!(instruction.length(), 2, "0xcb 0x{:02x}", opcode);
+        }
+    }
+
+    #[test]
+    fn decode_immediates() {
+        let (instruction, immediate) = Instruction::decode(&[0x3E, 0x42]).unwrap();
+        assert_eq!(instruction.length(), 2);
+        assert_eq!(immediate, Immediate::Byte(0x42));
+        let (_, immediate) = Instruction::decode(&[0xC3, 0x50, 0x01]).unwrap();
+        assert_eq!(immediate, Immediate::Word(0x0150));
+        let (_, immediate) = Instruction::decode(&[PREFIX, 0x7C]).unwrap();
+        assert_eq!(immediate, Immediate::None);
+        assert!(Instruction::decode(&[0xC3, 0x50]).is_none());
+        assert!(Instruction::decode(&[0xD3]).is_none());
+    }
+
+    /// Check that the flags are changed as declared, from every flag combination
+    fn check_flags(program: &[u8], name: &str) {
+        let instruction = Instruction::decode(program).unwrap().0;
+        let effects = instruction.flags();
+        let effects = [
+            (effects.zero, 0x80),
+            (effects.subtract, 0x40),
+            (effects.half_carry, 0x20),
+            (effects.carry, 0x10),
+        ];
+        for value in [0x00, 0x01, 0x0F, 0x80, 0x99, 0xFF].iter() {
+            for flags in [0x00u8, 0xF0].iter() {
+                let mut cpu = create_cpu_with_program(program);
+                cpu.registers.set_af(u16::from_be_bytes([*value, *flags]));
+                cpu.registers.set_bc(u16::from_be_bytes([*value, *value]));
+                cpu.registers.set_de(u16::from_be_bytes([*value, *value]));
+                cpu.registers.set_hl(0xC100);
+                cpu.memory.write_byte(0xC100, *value);
+                cpu.step();
+
+                let f = cpu.registers.af() as u8;
+                for (effect, mask) in effects.iter() {
+                    let expected = match effect {
+                        FlagEffect::Unchanged => flags & mask,
+                        FlagEffect::Reset => 0,
+                        FlagEffect::Set => *mask,
+                        FlagEffect::Computed => continue,
+                    };
+                    assert_eq!(
+                        f & mask,
+                        expected,
+                        "{} with A=0x{:02x} F=0x{:02x}, flag 0x{:02x}",
+                        name,
+                        value,
+                        flags,
+                        mask
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn flags() {
+        for opcode in 0..=0xFFu8 {
+            if opcode != PREFIX && Instruction::from_byte(opcode).is_some() {
+                check_flags(&[opcode, 0x01, 0xC0], &format!("0x{:02x}", opcode));
+            }
+            check_flags(&[PREFIX, opcode], &format!("0xcb 0x{:02x}", opcode));
+        }
+    }
+
+    #[test]
+    fn cycles() {
+        // JR NZ, not taken then taken
+        let cycles = Instruction::from_byte(0x20).unwrap().cycles();
+        assert_eq!((cycles.base, cycles.taken), (8, 12));
+        // BIT 7,(HL) then SET 7,(HL)
+        assert_eq!(
+            Instruction::from_prefixed_byte(0x7E).unwrap().cycles().base,
+            12
+        );
+        assert_eq!(
+            Instruction::from_prefixed_byte(0xFE).unwrap().cycles().base,
+            16
+        );
+    }
+}
+
+mod test_timing {
+    use super::*;
+    use crate::gameboy::cpu::Delay;
+
+    /// M-cycles of each opcode, branches not taken. 0 for the illegal opcodes.
+    #[rustfmt::skip]
+    const OPCODE_CYCLES: [Delay; 256] = [
+        1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1, // 0x
+        1, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 1x
+        2, 3, 2, 2, 1, 1, 2, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 2x
+        2, 3, 2, 2, 3, 3, 3, 1, 2, 2, 2, 2, 1, 1, 2, 1, // 3x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 4x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 5x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 6x
+        2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1, // 7x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 8x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 9x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // Ax
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // Bx
+        2, 3, 3, 4, 3, 4, 2, 4, 2, 4, 3, 0, 3, 6, 2, 4, // Cx
+        2, 3, 3, 0, 3, 4, 2, 4, 2, 4, 3, 0, 3, 0, 2, 4, // Dx
+        3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, // Ex
+        3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4, // Fx
+    ];
+
+    /// M-cycles of each opcode, branches taken
+    #[rustfmt::skip]
+    const OPCODE_CYCLES_TAKEN: [Delay; 256] = [
+        1, 3, 2, 2, 1, 1, 2, 1, 5, 2, 2, 2, 1, 1, 2, 1, // 0x
+        1, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 1x
+        3, 3, 2, 2, 1, 1, 2, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 2x
+        3, 3, 2, 2, 3, 3, 3, 1, 3, 2, 2, 2, 1, 1, 2, 1, // 3x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 4x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 5x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 6x
+        2, 2, 2, 2, 2, 2, 1, 2, 1, 1, 1, 1, 1, 1, 2, 1, // 7x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 8x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // 9x
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // Ax
+        1, 1, 1, 1, 1, 1, 2, 1, 1, 1, 1, 1, 1, 1, 2, 1, // Bx
+        5, 3, 4, 4, 6, 4, 2, 4, 5, 4, 4, 0, 6, 6, 2, 4, // Cx
+        5, 3, 4, 0, 6, 4, 2, 4, 5, 4, 4, 0, 6, 0, 2, 4, // Dx
+        3, 3, 2, 0, 0, 4, 2, 4, 4, 1, 4, 0, 0, 0, 2, 4, // Ex
+        3, 3, 2, 1, 0, 4, 2, 4, 3, 2, 4, 1, 0, 0, 2, 4, // Fx
+    ];
+
+    /// M-cycles of each opcode following 0xCB
+    #[rustfmt::skip]
+    const PREFIXED_CYCLES: [Delay; 256] = [
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 0x
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 1x
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 2x
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 3x
+        2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, // 4x
+        2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, // 5x
+        2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, // 6x
+        2, 2, 2, 2, 2, 2, 3, 2, 2, 2, 2, 2, 2, 2, 3, 2, // 7x
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 8x
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // 9x
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Ax
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Bx
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Cx
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Dx
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Ex
+        2, 2, 2, 2, 2, 2, 4, 2, 2, 2, 2, 2, 2, 2, 4, 2, // Fx
+    ];
+
+    /// Run the instruction with all the flags cleared, then all set.
+    /// One of them takes the conditional branches.
+    fn delays(program: &[u8]) -> [Delay; 2] {
+        let mut delays = [0; 2];
+        for (delay, flags) in delays.iter_mut().zip([0x00, 0xF0].iter()) {
+            let mut cpu = create_cpu_with_program(program);
+            cpu.registers.set_af(*flags);
+            cpu.registers.set_bc(0xC100);
+            cpu.registers.set_de(0xC100);
+            cpu.registers.set_hl(0xC100);
+            *delay = cpu.step();
+        }
+        delays.sort_unstable();
+        delays
+    }
+
+    #[test]
+    fn opcodes() {
+        for opcode in 0..=0xFFu8 {
+            let index = opcode as usize;
+            if OPCODE_CYCLES[index] == 0 || opcode == 0xCB {
+                continue;
+            }
+            let mut expected = [OPCODE_CYCLES[index] * 4, OPCODE_CYCLES_TAKEN[index] * 4];
+            expected.sort_unstable();
+            assert_eq!(
+                delays(&[opcode, 0x00, 0xC1]),
+                expected,
+                "opcode 0x{:02x}",
+                opcode
+            );
+        }
+    }
+
+    #[test]
+    fn prefixed_opcodes() {
+        for opcode in 0..=0xFFu8 {
+            let expected = PREFIXED_CYCLES[opcode as usize] * 4;
+            assert_eq!(
+                delays(&[0xCB, opcode]),
+                [expected; 2],
+                "opcode 0xcb 0x{:02x}",
+                opcode
+            );
+        }
+    }
+
+    /// TIMA increments every 16 T-cycles, the 4th M-cycle of an instruction sees it changed
+    fn create_cpu_with_fast_timer(program: &[u8]) -> Cpu {
+        let cpu = create_cpu_with_program(program);
+        cpu.memory.write_byte(0xFF07, 0x05);
+        cpu.memory.write_byte(0xFF05, 0x00);
+        cpu
+    }
+
+    #[test]
+    fn read_on_last_cycle() {
+        // LD A,(0xFF05)
+        let mut cpu = create_cpu_with_fast_timer(&[0xFA, 0x05, 0xFF]);
+        cpu.step();
+        assert_eq!(cpu.registers.a(), 0x01);
+
+        // LD A,(HL) twice: the first read happens on the 2nd M-cycle, the other on the 4th
+        let mut cpu = create_cpu_with_fast_timer(&[0x7E, 0x7E]);
+        cpu.registers.set_hl(0xFF05);
+        cpu.step();
+        assert_eq!(cpu.registers.a(), 0x00);
+        cpu.step();
+        assert_eq!(cpu.registers.a(), 0x01);
+    }
+
+    #[test]
+    fn write_on_last_cycle() {
+        // LD (0xFF05),A: written after the increment of the 4th M-cycle
+        let mut cpu = create_cpu_with_fast_timer(&[0xEA, 0x05, 0xFF]);
+        cpu.registers.set_a(0x80);
+        cpu.step();
+        assert_eq!(cpu.memory.read_byte(0xFF05), 0x80);
+    }
+
+    #[test]
+    fn internal_cycles_are_ticked() {
+        // PUSH BC then INC BC: 6 M-cycles, only 3 of them access the bus
+        let mut cpu = create_cpu_with_fast_timer(&[0xC5, 0x03, 0x7E]);
+        cpu.registers.set_hl(0xFF05);
+        assert_eq!(cpu.step() + cpu.step(), 24);
+        // LD A,(HL) reads on the 8th M-cycle
+        cpu.step();
+        assert_eq!(cpu.registers.a(), 0x02);
     }
 }
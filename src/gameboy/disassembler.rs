@@ -0,0 +1,384 @@
+use super::arithmetictarget::{ArithmeticTarget, WideArithmeticTarget};
+pub use super::instruction::{Cycles, FlagEffect, FlagEffects, Immediate};
+use super::instruction::{Instruction, JumpTest, JumpType, PREFIX};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Names of addresses, substituted to the raw values
+pub type Symbols = HashMap<u16, String>;
+
+/// Instruction decoded at an address
+pub struct Disassembled {
+    pub address: u16,
+    /// None for the illegal opcodes
+    pub(crate) instruction: Option<Instruction>,
+    pub opcode: u8,
+    pub immediate: Immediate,
+    /// In bytes, opcode and prefix included
+    pub length: u16,
+}
+
+/// Decode the instruction at the start of `bytes`, which are mapped at `address`.
+/// Return None if the bytes end before the instruction does.
+pub fn disassemble(bytes: &[u8], address: u16) -> Option<Disassembled> {
+    let opcode = *bytes.first()?;
+    let is_illegal = opcode != PREFIX && Instruction::from_byte(opcode).is_none();
+    if is_illegal {
+        return Some(Disassembled {
+            address,
+            instruction: None,
+            opcode,
+            immediate: Immediate::None,
+            length: 1,
+        });
+    }
+
+    let (instruction, immediate) = Instruction::decode(bytes)?;
+    Some(Disassembled {
+        address,
+        length: instruction.length(),
+        instruction: Some(instruction),
+        opcode: if opcode == PREFIX { bytes[1] } else { opcode },
+        immediate,
+    })
+}
+
+/// Decode the instructions following each other in `bytes`, which are mapped at `start`
+pub fn disassemble_range(bytes: &[u8], start: u16) -> Vec<Disassembled> {
+    let mut instructions = Vec::new();
+    let mut offset = 0;
+    while let Some(instruction) = bytes
+        .get(offset..)
+        .and_then(|bytes| disassemble(bytes, start.wrapping_add(offset as u16)))
+    {
+        offset += instruction.length as usize;
+        instructions.push(instruction);
+    }
+    instructions
+}
+
+/// Read a symbol file as written by rgblink, `bank:address name` on each line.
+/// Banks are ignored, comments start with `;`.
+pub fn parse_symbols(text: &str) -> Symbols {
+    text.lines()
+        .filter_map(|line| {
+            let line = line.split(';').next()?;
+            let mut parts = line.split_whitespace();
+            let location = parts.next()?;
+            let name = parts.next()?;
+            let address = location.rsplit(':').next()?;
+            let address = u16::from_str_radix(address, 16).ok()?;
+            Some((address, name.to_string()))
+        })
+        .collect()
+}
+
+impl Disassembled {
+    /// T-cycles taken, None for the illegal opcodes
+    pub fn cycles(&self) -> Option<Cycles> {
+        self.instruction.as_ref().map(Instruction::cycles)
+    }
+
+    /// Flags changed, None for the illegal opcodes
+    pub fn flags(&self) -> Option<FlagEffects> {
+        self.instruction.as_ref().map(Instruction::flags)
+    }
+
+    /// Render in RGBDS syntax, with the names of the known addresses
+    pub fn to_string_with(&self, symbols: &Symbols) -> String {
+        Renderer {
+            disassembled: self,
+            symbols: Some(symbols),
+        }
+        .to_string()
+    }
+}
+
+impl fmt::Display for Disassembled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Renderer {
+            disassembled: self,
+            symbols: None,
+        }
+        .fmt(f)
+    }
+}
+
+struct Renderer<'a> {
+    disassembled: &'a Disassembled,
+    symbols: Option<&'a Symbols>,
+}
+
+impl Renderer<'_> {
+    fn byte(&self) -> u8 {
+        match self.disassembled.immediate {
+            Immediate::Byte(byte) => byte,
+            _ => 0,
+        }
+    }
+
+    fn word(&self) -> u16 {
+        match self.disassembled.immediate {
+            Immediate::Word(word) => word,
+            _ => 0,
+        }
+    }
+
+    /// Name of the address, or its value
+    fn address(&self, address: u16) -> String {
+        match self.symbols.and_then(|symbols| symbols.get(&address)) {
+            Some(name) => name.clone(),
+            None => format!("${:04X}", address),
+        }
+    }
+
+    fn signed_byte(&self) -> String {
+        format!("{}", self.byte() as i8)
+    }
+
+    fn target(&self, target: &ArithmeticTarget) -> String {
+        match target {
+            ArithmeticTarget::A => "A".to_string(),
+            ArithmeticTarget::B => "B".to_string(),
+            ArithmeticTarget::C => "C".to_string(),
+            ArithmeticTarget::D => "D".to_string(),
+            ArithmeticTarget::E => "E".to_string(),
+            ArithmeticTarget::H => "H".to_string(),
+            ArithmeticTarget::L => "L".to_string(),
+            ArithmeticTarget::ReadByte => format!("${:02X}", self.byte()),
+            ArithmeticTarget::Pointer => format!("[{}]", self.address(self.word())),
+            ArithmeticTarget::FFRead => format!("[{}]", self.address(0xFF00 | self.byte() as u16)),
+            ArithmeticTarget::FFC => "[C]".to_string(),
+            ArithmeticTarget::BCTarget => "[BC]".to_string(),
+            ArithmeticTarget::DETarget => "[DE]".to_string(),
+            ArithmeticTarget::HLTarget => "[HL]".to_string(),
+            ArithmeticTarget::HLDec => "[HL-]".to_string(),
+            ArithmeticTarget::HLInc => "[HL+]".to_string(),
+        }
+    }
+
+    fn wide_target(&self, target: &WideArithmeticTarget) -> String {
+        match target {
+            WideArithmeticTarget::HL => "HL".to_string(),
+            WideArithmeticTarget::BC => "BC".to_string(),
+            WideArithmeticTarget::DE => "DE".to_string(),
+            WideArithmeticTarget::AF => "AF".to_string(),
+            WideArithmeticTarget::SP => "SP".to_string(),
+            WideArithmeticTarget::ReadWord => format!("${:04X}", self.word()),
+            WideArithmeticTarget::ReadAddress => format!("[{}]", self.address(self.word())),
+        }
+    }
+
+    /// Condition followed by a comma, if any
+    fn condition(test: &JumpTest) -> &'static str {
+        match test {
+            JumpTest::NotZero => "NZ,",
+            JumpTest::Zero => "Z,",
+            JumpTest::NotCarry => "NC,",
+            JumpTest::Carry => "C,",
+            JumpTest::Always => "",
+        }
+    }
+
+    /// Target of a relative jump, relative to the start of the instruction
+    fn relative_target(&self) -> String {
+        // The offset applies after the 2 bytes of the instruction
+        let offset = self.byte() as i8 as i32 + 2;
+        let target = self.disassembled.address.wrapping_add(offset as u16);
+        match self.symbols.and_then(|symbols| symbols.get(&target)) {
+            Some(name) => name.clone(),
+            None if offset < 0 => format!("$-{}", -offset),
+            None if offset > 0 => format!("$+{}", offset),
+            None => "$".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Renderer<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let instruction = match &self.disassembled.instruction {
+            Some(instruction) => instruction,
+            None => return write!(f, "DB ${:02X}", self.disassembled.opcode),
+        };
+        let alu = |f: &mut fmt::Formatter, mnemonic: &str, target: &ArithmeticTarget| {
+            write!(f, "{}{}", mnemonic, self.target(target))
+        };
+        match instruction {
+            Instruction::Nop => write!(f, "NOP"),
+            Instruction::Adc(target) => alu(f, "ADC A,", target),
+            Instruction::Add(target) => alu(f, "ADD A,", target),
+            Instruction::AddHL(target) => write!(f, "ADD HL,{}", self.wide_target(target)),
+            Instruction::AddSp => write!(f, "ADD SP,{}", self.signed_byte()),
+            Instruction::LoadHLSp => {
+                let offset = self.byte() as i8;
+                let sign = if offset < 0 { "" } else { "+" };
+                write!(f, "LD HL,SP{}{}", sign, offset)
+            }
+            Instruction::And(target) => alu(f, "AND ", target),
+            Instruction::Ccf => write!(f, "CCF"),
+            Instruction::Cp(target) => alu(f, "CP ", target),
+            Instruction::Cpl => write!(f, "CPL"),
+            Instruction::Dec(target) => alu(f, "DEC ", target),
+            Instruction::Dec16(target) => write!(f, "DEC {}", self.wide_target(target)),
+            Instruction::Inc(target) => alu(f, "INC ", target),
+            Instruction::Inc16(target) => write!(f, "INC {}", self.wide_target(target)),
+            Instruction::Or(target) => alu(f, "OR ", target),
+            Instruction::Rla => write!(f, "RLA"),
+            Instruction::Rlc(target) => alu(f, "RLC ", target),
+            Instruction::Rl(target) => alu(f, "RL ", target),
+            Instruction::Rr(target) => alu(f, "RR ", target),
+            Instruction::Rra => write!(f, "RRA"),
+            Instruction::Rrc(target) => alu(f, "RRC ", target),
+            Instruction::Rrca => write!(f, "RRCA"),
+            Instruction::Rlca => write!(f, "RLCA"),
+            Instruction::Sbc(target) => alu(f, "SBC A,", target),
+            Instruction::Sla(target) => alu(f, "SLA ", target),
+            Instruction::Sra(target) => alu(f, "SRA ", target),
+            Instruction::Srl(target) => alu(f, "SRL ", target),
+            Instruction::Sub(target) => alu(f, "SUB ", target),
+            Instruction::Swap(target) => alu(f, "SWAP ", target),
+            Instruction::Xor(target) => alu(f, "XOR ", target),
+            Instruction::Load { from, to } => {
+                let mnemonic = match (from, to) {
+                    (ArithmeticTarget::FFRead, _)
+                    | (ArithmeticTarget::FFC, _)
+                    | (_, ArithmeticTarget::FFRead)
+                    | (_, ArithmeticTarget::FFC) => "LDH",
+                    _ => "LD",
+                };
+                write!(f, "{} {},{}", mnemonic, self.target(to), self.target(from))
+            }
+            Instruction::Load16 { from, to } => {
+                write!(f, "LD {},{}", self.wide_target(to), self.wide_target(from))
+            }
+            Instruction::Jump(test, JumpType::Relative8) => {
+                write!(f, "JR {}{}", Self::condition(test), self.relative_target())
+            }
+            Instruction::Jump(test, JumpType::Pointer16) => write!(
+                f,
+                "JP {}{}",
+                Self::condition(test),
+                self.address(self.word())
+            ),
+            Instruction::Jump(_, JumpType::HL) => write!(f, "JP HL"),
+            Instruction::Push(target) => write!(f, "PUSH {}", self.wide_target(target)),
+            Instruction::Pop(target) => write!(f, "POP {}", self.wide_target(target)),
+            Instruction::Call(test) => write!(
+                f,
+                "CALL {}{}",
+                Self::condition(test),
+                self.address(self.word())
+            ),
+            Instruction::Rst(address) => write!(f, "RST ${:02X}", address),
+            Instruction::Ret(JumpTest::Always) => write!(f, "RET"),
+            Instruction::Ret(test) => {
+                write!(f, "RET {}", Self::condition(test).trim_end_matches(','))
+            }
+            Instruction::Reti => write!(f, "RETI"),
+            Instruction::DisableInterrupt => write!(f, "DI"),
+            Instruction::EnableInterrupt => write!(f, "EI"),
+            Instruction::Stop => write!(f, "STOP"),
+            Instruction::Bit(target, bit) => write!(f, "BIT {},{}", bit, self.target(target)),
+            Instruction::Set(target, bit) => write!(f, "SET {},{}", bit, self.target(target)),
+            Instruction::Res(target, bit) => write!(f, "RES {},{}", bit, self.target(target)),
+            Instruction::Halt => write!(f, "HALT"),
+            Instruction::Daa => write!(f, "DAA"),
+            Instruction::Scf => write!(f, "SCF"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(bytes: &[u8]) -> String {
+        disassemble(bytes, 0x0150).unwrap().to_string()
+    }
+
+    #[test]
+    fn mnemonics() {
+        assert_eq!(text(&[0x00]), "NOP");
+        assert_eq!(text(&[0x2A]), "LD A,[HL+]");
+        assert_eq!(text(&[0x32]), "LD [HL-],A");
+        assert_eq!(text(&[0x3E, 0x2A]), "LD A,$2A");
+        assert_eq!(text(&[0x01, 0x34, 0x12]), "LD BC,$1234");
+        assert_eq!(text(&[0x08, 0x00, 0xC1]), "LD [$C100],SP");
+        assert_eq!(text(&[0xFA, 0x00, 0xC1]), "LD A,[$C100]");
+        assert_eq!(text(&[0xE0, 0x44]), "LDH [$FF44],A");
+        assert_eq!(text(&[0xF2]), "LDH A,[C]");
+        assert_eq!(text(&[0xF9]), "LD SP,HL");
+        assert_eq!(text(&[0xF8, 0xFE]), "LD HL,SP-2");
+        assert_eq!(text(&[0xF8, 0x05]), "LD HL,SP+5");
+        assert_eq!(text(&[0xE8, 0x80]), "ADD SP,-128");
+        assert_eq!(text(&[0x80]), "ADD A,B");
+        assert_eq!(text(&[0xDE, 0x01]), "SBC A,$01");
+        assert_eq!(text(&[0xA6]), "AND [HL]");
+        assert_eq!(text(&[0x39]), "ADD HL,SP");
+        assert_eq!(text(&[0xF5]), "PUSH AF");
+        assert_eq!(text(&[0xFF]), "RST $38");
+        assert_eq!(text(&[0xC9]), "RET");
+        assert_eq!(text(&[0xD8]), "RET C");
+        assert_eq!(text(&[0xE9]), "JP HL");
+        assert_eq!(text(&[0xC2, 0x00, 0x02]), "JP NZ,$0200");
+        assert_eq!(text(&[0xCD, 0x00, 0x02]), "CALL $0200");
+        assert_eq!(text(&[0x10, 0x00]), "STOP");
+        assert_eq!(text(&[0xCB, 0x7C]), "BIT 7,H");
+        assert_eq!(text(&[0xCB, 0x36]), "SWAP [HL]");
+        assert_eq!(text(&[0xD3]), "DB $D3");
+    }
+
+    #[test]
+    fn relative_jumps() {
+        assert_eq!(text(&[0x20, 0xF9]), "JR NZ,$-5");
+        assert_eq!(text(&[0x18, 0xFE]), "JR $");
+        assert_eq!(text(&[0x38, 0x03]), "JR C,$+5");
+    }
+
+    #[test]
+    fn lengths() {
+        for opcode in 0..=0xFFu8 {
+            let disassembled = disassemble(&[opcode, 0x00, 0x00], 0).unwrap();
+            let expected = match opcode {
+                0xCB | 0x10 | 0x18 | 0x20 | 0x28 | 0x30 | 0x38 | 0xE0 | 0xE8 | 0xF0 | 0xF8 => 2,
+                0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x36 | 0x3E => 2,
+                0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => 2,
+                0x01 | 0x08 | 0x11 | 0x21 | 0x31 | 0xEA | 0xFA => 3,
+                0xC2 | 0xC3 | 0xC4 | 0xCA | 0xCC | 0xCD | 0xD2 | 0xD4 | 0xDA | 0xDC => 3,
+                _ => 1,
+            };
+            assert_eq!(disassembled.length, expected, "opcode 0x{:02x}", opcode);
+        }
+        // Truncated
+        assert!(disassemble(&[0xC3, 0x00], 0).is_none());
+        assert!(disassemble(&[0xCB], 0).is_none());
+        assert!(disassemble(&[], 0).is_none());
+    }
+
+    #[test]
+    fn range() {
+        // The entry point of most cartridges
+        let bytes = [0x00, 0xC3, 0x50, 0x01, 0xCB, 0x87, 0xE0];
+        let instructions = disassemble_range(&bytes, 0x0100);
+        let lines: Vec<_> = instructions
+            .iter()
+            .map(|instruction| format!("{:04X} {}", instruction.address, instruction))
+            .collect();
+        assert_eq!(lines, ["0100 NOP", "0101 JP $0150", "0104 RES 0,A"]);
+    }
+
+    #[test]
+    fn symbols() {
+        let symbols = parse_symbols("; File generated by rgblink\n00:0150 Main\n00:ff44 rLY\n");
+        assert_eq!(symbols.len(), 2);
+        let jump = disassemble(&[0xC3, 0x50, 0x01], 0x0100).unwrap();
+        assert_eq!(jump.to_string_with(&symbols), "JP Main");
+        let load = disassemble(&[0xF0, 0x44], 0x0150).unwrap();
+        assert_eq!(load.to_string_with(&symbols), "LDH A,[rLY]");
+        let loop_back = disassemble(&[0x18, 0xFE], 0x0150).unwrap();
+        assert_eq!(loop_back.to_string_with(&symbols), "JR Main");
+        // Unknown addresses are left as is
+        let call = disassemble(&[0xCD, 0x00, 0x02], 0x0100).unwrap();
+        assert_eq!(call.to_string_with(&symbols), "CALL $0200");
+    }
+}
@@ -0,0 +1,76 @@
+use super::cartridge::Mapper;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors raised while loading a cartridge or building the emulator
+#[derive(Debug)]
+pub enum Error {
+    /// A file (ROM, boot ROM, save) could not be read
+    Io { path: PathBuf, source: io::Error },
+    /// The ROM is too small to contain a header
+    RomTooSmall(usize),
+    /// Undocumented cartridge type at 0x0147
+    UnknownCartridgeType(u8),
+    /// Undocumented ROM size at 0x0148
+    UnknownRomSize(u8),
+    /// Undocumented RAM size at 0x0149
+    UnknownRamSize(u8),
+    /// The cartridge needs hardware that is not emulated
+    UnsupportedMapper { code: u8, mapper: Mapper },
+    /// The cartridge runs on Game Boy Color only
+    CgbOnly { title: String },
+    /// The boot ROM must be exactly 0x100 bytes
+    BootRomSize(usize),
+    /// The battery save does not match the cartridge
+    InvalidSaveSize(usize),
+    /// Unknown hardware model name
+    UnknownModel(String),
+    /// The trace file could not be created
+    Trace { path: PathBuf, source: io::Error },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io { path, source } => {
+                write!(f, "Failed to read {} : {}", path.display(), source)
+            }
+            Error::RomTooSmall(size) => {
+                write!(f, "ROM too small to contain a header : {} bytes", size)
+            }
+            Error::UnknownCartridgeType(code) => {
+                write!(f, "Unknown cartridge type : 0x{:02x}", code)
+            }
+            Error::UnknownRomSize(code) => write!(f, "Unknown ROM size : 0x{:02x}", code),
+            Error::UnknownRamSize(code) => write!(f, "Unknown RAM size : 0x{:02x}", code),
+            Error::UnsupportedMapper { code, mapper } => {
+                write!(
+                    f,
+                    "Unsupported cartridge type 0x{:02x} : {:?}",
+                    code, mapper
+                )
+            }
+            Error::CgbOnly { title } => write!(f, "{} runs on Game Boy Color only", title),
+            Error::BootRomSize(size) => write!(
+                f,
+                "Invalid boot ROM size : {} bytes, expected 256 bytes",
+                size
+            ),
+            Error::InvalidSaveSize(size) => write!(f, "Invalid save size : {} bytes", size),
+            Error::UnknownModel(name) => write!(f, "Unknown model : {}", name),
+            Error::Trace { path, source } => {
+                write!(f, "Failed to create trace {} : {}", path.display(), source)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } | Error::Trace { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
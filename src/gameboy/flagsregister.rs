@@ -2,8 +2,6 @@ const ZERO_FLAG_BYTE_POSITION: u8 = 7;
 const SUBTRACT_FLAG_BYTE_POSITION: u8 = 6;
 const HALF_CARRY_FLAG_BYTE_POSITION: u8 = 5;
 const CARRY_FLAG_BYTE_POSITION: u8 = 4;
-/// CHECKME
-const EMI_FLAG_BYTE_POSITION: u8 = 3;
 
 #[derive(Copy, Clone, Debug)]
 /// Contain all the CPU flags.
@@ -13,8 +11,6 @@ pub struct FlagsRegister {
     subtract: bool,
     half_carry: bool,
     carry: bool,
-    /// Interrupt Master Enabled flag
-    emi: bool,
 }
 
 impl FlagsRegister {
@@ -34,10 +30,6 @@ impl FlagsRegister {
         self.carry
     }
 
-    pub fn emi(&self) -> bool {
-        self.emi
-    }
-
     pub fn set_zero(&mut self, zero: bool) {
         self.zero = zero
     }
@@ -53,10 +45,6 @@ impl FlagsRegister {
     pub fn set_carry(&mut self, carry: bool) {
         self.carry = carry
     }
-
-    pub fn set_emi(&mut self, interrupt_enabled: bool) {
-        self.emi = interrupt_enabled;
-    }
 }
 
 impl std::convert::From<FlagsRegister> for u8 {
@@ -65,7 +53,6 @@ impl std::convert::From<FlagsRegister> for u8 {
             | (if flag.subtract { 1 } else { 0 }) << SUBTRACT_FLAG_BYTE_POSITION
             | (if flag.half_carry { 1 } else { 0 }) << HALF_CARRY_FLAG_BYTE_POSITION
             | (if flag.carry { 1 } else { 0 }) << CARRY_FLAG_BYTE_POSITION
-            | (if flag.emi { 1 } else { 0 } << EMI_FLAG_BYTE_POSITION)
     }
 }
 
@@ -81,14 +68,12 @@ impl std::convert::From<u8> for FlagsRegister {
         let subtract = ((byte >> SUBTRACT_FLAG_BYTE_POSITION) & 0b1) != 0;
         let half_carry = ((byte >> HALF_CARRY_FLAG_BYTE_POSITION) & 0b1) != 0;
         let carry = ((byte >> CARRY_FLAG_BYTE_POSITION) & 0b1) != 0;
-        let emi = ((byte >> EMI_FLAG_BYTE_POSITION) & 0b1) != 0;
 
         FlagsRegister {
             zero,
             subtract,
             half_carry,
             carry,
-            emi,
         }
     }
 }
@@ -22,7 +22,7 @@ pub enum TileMap {
 impl Gpu {
     #[inline]
     fn lcd_control_register(&self) -> u8 {
-        self.memory.read_byte(LCD_CONTROL_REGISTER_ADDRESS)
+        self.memory.read_io(LCD_CONTROL_REGISTER_ADDRESS)
     }
 
     /// Return true if the LCD screen is enabled
@@ -4,7 +4,7 @@ mod pixel;
 use lcd_control_register::*;
 use pixel::Pixel;
 
-use super::memory::SharedMemory;
+use super::memory::{Interrupt, SharedMemory};
 use crate::gameboy::memory::VideoRam;
 
 use glium::{texture::ClientFormat, Surface};
@@ -77,7 +77,6 @@ enum _ModeFlag {
 
 const SCY_ADRESS: u16 = 0xFF42;
 const SCX_ADRESS: u16 = 0xFF43;
-const LCDY_ADRESS: u16 = 0xFF44;
 
 /// Inside the Window f Winit, we will need to create a Vulkan context
 impl Gpu {
@@ -91,12 +90,12 @@ impl Gpu {
 
     /// Scroll Y
     pub fn scy(&self) -> u8 {
-        self.memory.read_byte(SCY_ADRESS)
+        self.memory.read_io(SCY_ADRESS)
     }
 
     /// Scroll X
     pub fn scx(&self) -> u8 {
-        self.memory.read_byte(SCX_ADRESS)
+        self.memory.read_io(SCX_ADRESS)
     }
 
     /// Read the background tilemaps and write them to the buffers
@@ -120,7 +119,7 @@ impl Gpu {
 
         // This should be optimized in a shader
         for pixel_row in 0..SCREEN_H {
-            self.memory.write_byte(LCDY_ADRESS, pixel_row as u8);
+            self.memory.set_ly(pixel_row as u8);
             for pixel_col in 0..SCREEN_W {
                 //  make the value wrap when out of bound due to SCX / SCY
                 let tilemap_pixel_row = (pixel_row + scy) % 255;
@@ -149,7 +148,8 @@ impl Gpu {
             }
         }
 
-        self.memory.write_byte(LCDY_ADRESS, 144);
+        self.memory.set_ly(144);
+        self.memory.request_interrupt(Interrupt::VBlank);
     }
 
     /// Convert tilemap row and col - including SCY and SCX - to tileindex and offset within the
@@ -1,6 +1,9 @@
 use super::arithmetictarget::{ArithmeticTarget, WideArithmeticTarget};
 use super::flagsregister::FlagsRegister;
 
+/// Opcode prefixing the bit operations
+pub const PREFIX: u8 = 0xCB;
+
 #[derive(Debug)]
 pub enum JumpTest {
     Zero,
@@ -28,8 +31,6 @@ impl JumpTest {
 #[derive(Debug)]
 pub enum JumpType {
     Relative8,
-    _Relative16,
-    _Pointer8,
     Pointer16,
     // Jump to the address in HL
     HL,
@@ -43,6 +44,8 @@ pub enum Instruction {
     AddSp,
     // ADD operation to the HL register
     AddHL(WideArithmeticTarget),
+    // Load SP plus the next signed byte in HL
+    LoadHLSp,
     And(ArithmeticTarget),
     Ccf,
     Cp(ArithmeticTarget),
@@ -108,6 +111,8 @@ pub enum Instruction {
 }
 
 impl Instruction {
+    /// Decode an unprefixed opcode.
+    /// Return None for the 0xCB prefix and the illegal opcodes, which lock the CPU.
     pub fn from_byte(byte: u8) -> Option<Instruction> {
         match byte {
             0x00 => Some(Instruction::Nop),
@@ -195,12 +200,12 @@ impl Instruction {
             }),
             0x27 => Some(Instruction::Daa),
             0x28 => Some(Instruction::Jump(JumpTest::Zero, JumpType::Relative8)),
-            0x29 => unimplemented!("0x{:x}", byte),
+            0x29 => Some(Instruction::AddHL(WideArithmeticTarget::HL)),
             0x2a => Some(Instruction::Load {
                 from: ArithmeticTarget::HLInc,
                 to: ArithmeticTarget::A,
             }),
-            0x2b => unimplemented!("0x{:x}", byte),
+            0x2b => Some(Instruction::Dec16(WideArithmeticTarget::HL)),
             0x2c => Some(Instruction::Inc(ArithmeticTarget::L)),
             0x2d => Some(Instruction::Dec(ArithmeticTarget::L)),
             0x2e => Some(Instruction::Load {
@@ -299,7 +304,7 @@ impl Instruction {
             }),
             0x4e => Some(Instruction::Load {
                 to: ArithmeticTarget::C,
-                from: ArithmeticTarget::B,
+                from: ArithmeticTarget::HLTarget,
             }),
             0x4f => Some(Instruction::Load {
                 to: ArithmeticTarget::C,
@@ -331,7 +336,7 @@ impl Instruction {
             }),
             0x56 => Some(Instruction::Load {
                 to: ArithmeticTarget::D,
-                from: ArithmeticTarget::B,
+                from: ArithmeticTarget::HLTarget,
             }),
             0x57 => Some(Instruction::Load {
                 to: ArithmeticTarget::D,
@@ -363,7 +368,7 @@ impl Instruction {
             }),
             0x5e => Some(Instruction::Load {
                 to: ArithmeticTarget::E,
-                from: ArithmeticTarget::B,
+                from: ArithmeticTarget::HLTarget,
             }),
             0x5f => Some(Instruction::Load {
                 to: ArithmeticTarget::E,
@@ -395,7 +400,7 @@ impl Instruction {
             }),
             0x66 => Some(Instruction::Load {
                 to: ArithmeticTarget::H,
-                from: ArithmeticTarget::B,
+                from: ArithmeticTarget::HLTarget,
             }),
             0x67 => Some(Instruction::Load {
                 to: ArithmeticTarget::H,
@@ -580,8 +585,9 @@ impl Instruction {
             0xc8 => Some(Instruction::Ret(JumpTest::Zero)),
             0xc9 => Some(Instruction::Ret(JumpTest::Always)),
             0xca => Some(Instruction::Jump(JumpTest::Zero, JumpType::Pointer16)),
-            0xcb => unimplemented!("0x{:x}", byte),
-            0xcc => unimplemented!("0x{:x}", byte),
+            // Prefix, see `from_prefixed_byte`
+            0xcb => None,
+            0xcc => Some(Instruction::Call(JumpTest::Zero)),
             0xcd => Some(Instruction::Call(JumpTest::Always)),
             0xce => Some(Instruction::Adc(ArithmeticTarget::ReadByte)),
             0xcf => Some(Instruction::Rst(0x08)),
@@ -589,7 +595,7 @@ impl Instruction {
             0xd1 => Some(Instruction::Pop(WideArithmeticTarget::DE)),
             0xd2 => Some(Instruction::Jump(JumpTest::NotCarry, JumpType::Pointer16)),
             0xd3 => None,
-            0xd4 => unimplemented!("0x{:x}", byte),
+            0xd4 => Some(Instruction::Call(JumpTest::NotCarry)),
             0xd5 => Some(Instruction::Push(WideArithmeticTarget::DE)),
             0xd6 => Some(Instruction::Sub(ArithmeticTarget::ReadByte)),
             0xd7 => Some(Instruction::Rst(0x10)),
@@ -598,9 +604,9 @@ impl Instruction {
             0xd9 => Some(Instruction::Reti),
             0xda => Some(Instruction::Jump(JumpTest::Carry, JumpType::Pointer16)),
             0xdb => None,
-            0xdc => unimplemented!("0x{:x}", byte),
+            0xdc => Some(Instruction::Call(JumpTest::Carry)),
             0xdd => None,
-            0xde => unimplemented!("0x{:x}", byte),
+            0xde => Some(Instruction::Sbc(ArithmeticTarget::ReadByte)),
             0xdf => Some(Instruction::Rst(0x18)),
             0xe0 => Some(Instruction::Load {
                 to: ArithmeticTarget::FFRead,
@@ -625,7 +631,7 @@ impl Instruction {
             0xeb => None,
             0xec => None,
             0xed => None,
-            0xee => unimplemented!("0x{:x}", byte),
+            0xee => Some(Instruction::Xor(ArithmeticTarget::ReadByte)),
             0xef => Some(Instruction::Rst(0x28)),
             0xf0 => Some(Instruction::Load {
                 from: ArithmeticTarget::FFRead,
@@ -641,7 +647,7 @@ impl Instruction {
             0xf5 => Some(Instruction::Push(WideArithmeticTarget::AF)),
             0xf6 => Some(Instruction::Or(ArithmeticTarget::ReadByte)),
             0xf7 => Some(Instruction::Rst(0x30)),
-            0xf8 => unimplemented!("0x{:x}", byte),
+            0xf8 => Some(Instruction::LoadHLSp),
             0xf9 => Some(Instruction::Load16 {
                 from: WideArithmeticTarget::HL,
                 to: WideArithmeticTarget::SP,
@@ -931,56 +937,261 @@ impl Instruction {
     }
 }
 
-impl ToString for Instruction {
-    fn to_string(&self) -> String {
+/// Operand bytes following the opcode
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Immediate {
+    None,
+    Byte(u8),
+    Word(u16),
+}
+
+/// T-cycles taken by an instruction
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Cycles {
+    /// Conditional branch not taken, or any other instruction
+    pub base: u32,
+    /// Branch taken, same as `base` for the other instructions
+    pub taken: u32,
+}
+
+/// How an instruction changes a flag
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum FlagEffect {
+    Unchanged,
+    Reset,
+    Set,
+    /// Depends on the operands
+    Computed,
+}
+
+/// Effect of an instruction on each flag, written `Z N H C` in the opcode tables
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FlagEffects {
+    pub zero: FlagEffect,
+    pub subtract: FlagEffect,
+    pub half_carry: FlagEffect,
+    pub carry: FlagEffect,
+}
+
+impl FlagEffects {
+    const fn new(
+        zero: FlagEffect,
+        subtract: FlagEffect,
+        half_carry: FlagEffect,
+        carry: FlagEffect,
+    ) -> Self {
+        Self {
+            zero,
+            subtract,
+            half_carry,
+            carry,
+        }
+    }
+}
+
+/// M-cycles of the memory accesses of a target, operands included
+fn access_cycles(target: &ArithmeticTarget) -> u32 {
+    match target {
+        ArithmeticTarget::A
+        | ArithmeticTarget::B
+        | ArithmeticTarget::C
+        | ArithmeticTarget::D
+        | ArithmeticTarget::E
+        | ArithmeticTarget::H
+        | ArithmeticTarget::L => 0,
+        ArithmeticTarget::FFRead => 2,
+        ArithmeticTarget::Pointer => 3,
+        _ => 1,
+    }
+}
+
+/// Bytes of operand of a target
+fn operand_size(target: &ArithmeticTarget) -> u16 {
+    match target {
+        ArithmeticTarget::ReadByte | ArithmeticTarget::FFRead => 1,
+        ArithmeticTarget::Pointer => 2,
+        _ => 0,
+    }
+}
+
+impl Instruction {
+    /// Decode the instruction at the start of `bytes`, with its operand.
+    /// Return None for the illegal opcodes, or if the bytes end before the instruction does.
+    pub fn decode(bytes: &[u8]) -> Option<(Instruction, Immediate)> {
+        let opcode = *bytes.first()?;
+        let instruction = if opcode == PREFIX {
+            Instruction::from_prefixed_byte(*bytes.get(1)?)?
+        } else {
+            Instruction::from_byte(opcode)?
+        };
+        let immediate = match instruction.immediate_size() {
+            0 => Immediate::None,
+            1 => Immediate::Byte(*bytes.get(1)?),
+            _ => Immediate::Word(u16::from_le_bytes([*bytes.get(1)?, *bytes.get(2)?])),
+        };
+        Some((instruction, immediate))
+    }
+
+    /// Size in bytes, prefix and operand included
+    pub fn length(&self) -> u16 {
+        let opcode = if self.is_prefixed() { 2 } else { 1 };
+        opcode + self.immediate_size()
+    }
+
+    /// Bytes of operand following the opcode, the ignored byte following STOP included
+    pub fn immediate_size(&self) -> u16 {
+        match self {
+            Instruction::Adc(target)
+            | Instruction::Add(target)
+            | Instruction::And(target)
+            | Instruction::Cp(target)
+            | Instruction::Or(target)
+            | Instruction::Sbc(target)
+            | Instruction::Sub(target)
+            | Instruction::Xor(target) => operand_size(target),
+            Instruction::Load { from, to } => operand_size(from) + operand_size(to),
+            Instruction::Load16 {
+                from: WideArithmeticTarget::ReadWord,
+                ..
+            }
+            | Instruction::Load16 {
+                to: WideArithmeticTarget::ReadAddress,
+                ..
+            } => 2,
+            Instruction::Jump(_, JumpType::Relative8) => 1,
+            Instruction::Jump(_, JumpType::Pointer16) | Instruction::Call(_) => 2,
+            Instruction::AddSp | Instruction::LoadHLSp | Instruction::Stop => 1,
+            _ => 0,
+        }
+    }
+
+    /// Instructions following the 0xCB prefix
+    pub fn is_prefixed(&self) -> bool {
+        matches!(
+            self,
+            Instruction::Rlc(_)
+                | Instruction::Rrc(_)
+                | Instruction::Rl(_)
+                | Instruction::Rr(_)
+                | Instruction::Sla(_)
+                | Instruction::Sra(_)
+                | Instruction::Swap(_)
+                | Instruction::Srl(_)
+                | Instruction::Bit(_, _)
+                | Instruction::Res(_, _)
+                | Instruction::Set(_, _)
+        )
+    }
+
+    /// T-cycles taken, 4 per M-cycle
+    pub fn cycles(&self) -> Cycles {
+        // M-cycles, not taken then taken
+        let (base, taken) = match self {
+            Instruction::Adc(target)
+            | Instruction::Add(target)
+            | Instruction::And(target)
+            | Instruction::Cp(target)
+            | Instruction::Or(target)
+            | Instruction::Sbc(target)
+            | Instruction::Sub(target)
+            | Instruction::Xor(target) => (1 + access_cycles(target), 0),
+            // Read then write back
+            Instruction::Inc(target) | Instruction::Dec(target) => {
+                (1 + 2 * access_cycles(target), 0)
+            }
+            Instruction::Load { from, to } => (1 + access_cycles(from) + access_cycles(to), 0),
+            Instruction::Load16 {
+                from: WideArithmeticTarget::ReadWord,
+                ..
+            } => (3, 0),
+            Instruction::Load16 {
+                to: WideArithmeticTarget::ReadAddress,
+                ..
+            } => (5, 0),
+            // LD SP,HL
+            Instruction::Load16 { .. } => (2, 0),
+            Instruction::Inc16(_) | Instruction::Dec16(_) | Instruction::AddHL(_) => (2, 0),
+            Instruction::AddSp => (4, 0),
+            Instruction::LoadHLSp => (3, 0),
+            Instruction::Rlc(target)
+            | Instruction::Rrc(target)
+            | Instruction::Rl(target)
+            | Instruction::Rr(target)
+            | Instruction::Sla(target)
+            | Instruction::Sra(target)
+            | Instruction::Swap(target)
+            | Instruction::Srl(target)
+            | Instruction::Res(target, _)
+            | Instruction::Set(target, _) => (2 + 2 * access_cycles(target), 0),
+            Instruction::Bit(target, _) => (2 + access_cycles(target), 0),
+            Instruction::Push(_) | Instruction::Rst(_) => (4, 0),
+            Instruction::Pop(_) => (3, 0),
+            Instruction::Jump(JumpTest::Always, JumpType::HL) => (1, 0),
+            Instruction::Jump(JumpTest::Always, JumpType::Relative8) => (3, 0),
+            Instruction::Jump(JumpTest::Always, JumpType::Pointer16) => (4, 0),
+            Instruction::Jump(_, JumpType::Relative8) => (2, 3),
+            Instruction::Jump(_, _) => (3, 4),
+            Instruction::Call(JumpTest::Always) => (6, 0),
+            Instruction::Call(_) => (3, 6),
+            Instruction::Ret(JumpTest::Always) | Instruction::Reti => (4, 0),
+            // Checking the condition takes a cycle
+            Instruction::Ret(_) => (2, 5),
+            Instruction::Nop
+            | Instruction::Rla
+            | Instruction::Rra
+            | Instruction::Rlca
+            | Instruction::Rrca
+            | Instruction::Ccf
+            | Instruction::Cpl
+            | Instruction::Scf
+            | Instruction::Daa
+            | Instruction::DisableInterrupt
+            | Instruction::EnableInterrupt
+            | Instruction::Halt
+            | Instruction::Stop => (1, 0),
+        };
+        // Unconditional instructions always take the same time
+        let taken = if taken == 0 { base } else { taken };
+        Cycles {
+            base: base * 4,
+            taken: taken * 4,
+        }
+    }
+
+    /// Flags changed by the instruction
+    pub fn flags(&self) -> FlagEffects {
+        use FlagEffect::{Computed as X, Reset as O, Set as I, Unchanged as U};
         match self {
-            Instruction::Nop => "nop".to_string(),
-            Instruction::Adc(_) => "adc".to_string(),
-            Instruction::Add(_) => "add".to_string(),
-            Instruction::AddHL(_) => "addhl".to_string(),
-            Instruction::AddSp => "add sp".to_string(),
-            Instruction::And(_) => "and".to_string(),
-            Instruction::Ccf => "ccf".to_string(),
-            Instruction::Cp(target) => format!("cp {:?}", target),
-            Instruction::Cpl => "cpl".to_string(),
-            Instruction::Dec(target) => format!("dec {:?}", target),
-            Instruction::Dec16(target) => format!("dec16 {:?}", target),
-            Instruction::Inc(target) => format!("inc {:?}", target),
-            Instruction::Inc16(target) => format!("inc16 {:?}", target),
-            Instruction::Or(_) => "or".to_string(),
-            Instruction::Rla => "rla".to_string(),
-            Instruction::Rlc(_) => "rlc".to_string(),
-            Instruction::Rl(target) => format!("rl {:?}", target),
-            Instruction::Rr(_) => "rr".to_string(),
-            Instruction::Rra => "rra".to_string(),
-            Instruction::Rrc(_) => "rrc".to_string(),
-            Instruction::Rrca => "rrca".to_string(),
-            Instruction::Rlca => "rlca".to_string(),
-            Instruction::Sbc(_) => "sbc".to_string(),
-            Instruction::Sla(_) => "sla".to_string(),
-            Instruction::Sra(_) => "sra".to_string(),
-            Instruction::Srl(_) => "srl".to_string(),
-            Instruction::Sub(_) => "sub".to_string(),
-            Instruction::Swap(_) => "swap".to_string(),
-            Instruction::Xor(target) => format!("xor {:?}^A", target),
-            Instruction::Load { from, to } => format!("load {:?}->{:?}", from, to),
-            Instruction::Load16 { from, to } => format!("load16 {:?}->{:?}", from, to),
-            Instruction::Jump(test, kind) => format!("jump-{:?}-{:?}", test, kind),
-            Instruction::Push(target) => format!("push {:?} to stack", target),
-            Instruction::Pop(_) => "pop".to_string(),
-            Instruction::Call(_) => "call".to_string(),
-            Instruction::Ret(_) => "ret".to_string(),
-            Instruction::Reti => "reti".to_string(),
-            Instruction::Bit(target, bit_number) => format!("bit {} of {:?}", bit_number, target),
-            Instruction::Set(_, _) => "set".to_string(),
-            Instruction::Res(_, _) => "res".to_string(),
-            Instruction::Halt => "halt".to_string(),
-            Instruction::DisableInterrupt => "disable interrupt".to_string(),
-            Instruction::EnableInterrupt => "enable interrupt".to_string(),
-            Instruction::Stop => "Stop".to_string(),
-            Instruction::Rst(address) => format!("Reset to address {:02x}", address),
-            Instruction::Scf => "Set Carry Flag".to_string(),
-            Instruction::Daa => "DAA".to_string(),
+            Instruction::Add(_) | Instruction::Adc(_) => FlagEffects::new(X, O, X, X),
+            Instruction::Sub(_) | Instruction::Sbc(_) | Instruction::Cp(_) => {
+                FlagEffects::new(X, I, X, X)
+            }
+            Instruction::And(_) => FlagEffects::new(X, O, I, O),
+            Instruction::Xor(_) | Instruction::Or(_) | Instruction::Swap(_) => {
+                FlagEffects::new(X, O, O, O)
+            }
+            Instruction::Inc(_) => FlagEffects::new(X, O, X, U),
+            Instruction::Dec(_) => FlagEffects::new(X, I, X, U),
+            Instruction::AddHL(_) => FlagEffects::new(U, O, X, X),
+            Instruction::AddSp | Instruction::LoadHLSp => FlagEffects::new(O, O, X, X),
+            Instruction::Rla | Instruction::Rra | Instruction::Rlca | Instruction::Rrca => {
+                FlagEffects::new(O, O, O, X)
+            }
+            Instruction::Rlc(_)
+            | Instruction::Rrc(_)
+            | Instruction::Rl(_)
+            | Instruction::Rr(_)
+            | Instruction::Sla(_)
+            | Instruction::Sra(_)
+            | Instruction::Srl(_) => FlagEffects::new(X, O, O, X),
+            Instruction::Bit(_, _) => FlagEffects::new(X, O, I, U),
+            Instruction::Daa => FlagEffects::new(X, U, O, X),
+            Instruction::Cpl => FlagEffects::new(U, I, I, U),
+            Instruction::Scf => FlagEffects::new(U, O, O, I),
+            Instruction::Ccf => FlagEffects::new(U, O, O, X),
+            // F is popped from the stack
+            Instruction::Pop(WideArithmeticTarget::AF) => FlagEffects::new(X, X, X, X),
+            _ => FlagEffects::new(U, U, U, U),
         }
     }
 }
@@ -0,0 +1,84 @@
+use std::ops::Range;
+
+/// Number of bytes copied to the sprite attribute table
+pub const OAM_DMA_LENGTH: u16 = 0xA0;
+/// One byte is copied each M-cycle
+const CYCLES_PER_BYTE: u32 = 4;
+
+/// OAM DMA, started by writing the source high byte to 0xFF46.
+/// Copies 0xXX00-0xXX9F to 0xFE00-0xFE9F in 160 M-cycles, during which the CPU can only
+/// access the high RAM.
+#[derive(Debug, Default)]
+pub struct OamDma {
+    source: u16,
+    /// Bytes copied so far, None when no transfer is running
+    copied: Option<u16>,
+    /// T-cycles not yet spent on a byte
+    cycles: u32,
+}
+
+impl OamDma {
+    /// State left by the boot ROM, which never starts a transfer
+    pub fn post_boot() -> Self {
+        Self {
+            source: 0xFF00,
+            ..Self::default()
+        }
+    }
+
+    /// Start a transfer from 0xXX00, restarting any running one
+    pub fn start(&mut self, value: u8) {
+        self.source = (value as u16) << 8;
+        self.copied = Some(0);
+        self.cycles = 0;
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.copied.is_some()
+    }
+
+    pub fn source(&self) -> u16 {
+        self.source
+    }
+
+    /// Advance by some T-cycles, returns the offsets of the bytes to copy meanwhile
+    pub fn tick(&mut self, cycles: u32) -> Range<u16> {
+        let copied = match self.copied {
+            Some(copied) => copied,
+            None => return 0..0,
+        };
+        self.cycles += cycles;
+        let bytes = (self.cycles / CYCLES_PER_BYTE).min((OAM_DMA_LENGTH - copied) as u32) as u16;
+        self.cycles %= CYCLES_PER_BYTE;
+
+        let end = copied + bytes;
+        self.copied = if end == OAM_DMA_LENGTH {
+            None
+        } else {
+            Some(end)
+        };
+        copied..end
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_length() {
+        let mut dma = OamDma::default();
+        assert_eq!(dma.tick(4), 0..0);
+
+        dma.start(0xC1);
+        assert_eq!(dma.source(), 0xC100);
+        assert_eq!(dma.tick(2), 0..0);
+        assert_eq!(dma.tick(2), 0..1);
+        assert_eq!(dma.tick(8), 1..3);
+        assert_eq!(dma.tick(4 * 156), 3..159);
+        assert!(dma.is_active());
+        assert_eq!(dma.tick(400), 159..160);
+        assert!(!dma.is_active());
+        assert_eq!(dma.tick(4), 0..0);
+    }
+}
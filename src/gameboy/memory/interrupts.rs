@@ -0,0 +1,129 @@
+/// Interrupt flag register
+pub const IF: u16 = 0xFF0F;
+
+/// One bit per interrupt source
+const INTERRUPTS_MASK: u8 = 0b0001_1111;
+
+/// Interrupt sources, by decreasing priority
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Interrupt {
+    VBlank,
+    Stat,
+    Timer,
+    Serial,
+    Joypad,
+}
+
+impl Interrupt {
+    /// All sources, the first one has the highest priority
+    pub const ALL: [Interrupt; 5] = [
+        Interrupt::VBlank,
+        Interrupt::Stat,
+        Interrupt::Timer,
+        Interrupt::Serial,
+        Interrupt::Joypad,
+    ];
+
+    /// Bit in IF and IE
+    pub fn mask(self) -> u8 {
+        1 << self as u8
+    }
+
+    /// Address of the handler
+    pub fn vector(self) -> u16 {
+        0x0040 + 8 * self as u16
+    }
+}
+
+/// Interrupt controller : requested interrupts in IF (0xFF0F),
+/// enabled ones in IE (0xFFFF)
+#[derive(Debug, Default)]
+pub struct Interrupts {
+    requested: u8,
+    enabled: u8,
+}
+
+impl Interrupts {
+    /// State left by the boot ROM : VBlank requested
+    pub fn post_boot() -> Self {
+        Self {
+            requested: Interrupt::VBlank.mask(),
+            enabled: 0x00,
+        }
+    }
+
+    /// IF, unused bits read as 1
+    pub fn read_flag(&self) -> u8 {
+        self.requested | !INTERRUPTS_MASK
+    }
+
+    pub fn write_flag(&mut self, value: u8) {
+        self.requested = value & INTERRUPTS_MASK;
+    }
+
+    /// IE, all the bits can be read back
+    pub fn read_enable(&self) -> u8 {
+        self.enabled
+    }
+
+    pub fn write_enable(&mut self, value: u8) {
+        self.enabled = value;
+    }
+
+    pub fn request(&mut self, interrupt: Interrupt) {
+        self.requested |= interrupt.mask();
+    }
+
+    /// Clear the request once the CPU jumps to the handler
+    pub fn acknowledge(&mut self, interrupt: Interrupt) {
+        self.requested &= !interrupt.mask();
+    }
+
+    /// Requested and enabled interrupt with the highest priority
+    pub fn pending(&self) -> Option<Interrupt> {
+        let pending = self.requested & self.enabled;
+        Interrupt::ALL
+            .iter()
+            .copied()
+            .find(|interrupt| pending & interrupt.mask() != 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vectors() {
+        assert_eq!(Interrupt::VBlank.vector(), 0x40);
+        assert_eq!(Interrupt::Stat.vector(), 0x48);
+        assert_eq!(Interrupt::Joypad.vector(), 0x60);
+        assert_eq!(Interrupt::Timer.mask(), 0x04);
+    }
+
+    #[test]
+    fn priority() {
+        let mut interrupts = Interrupts::default();
+        interrupts.request(Interrupt::Joypad);
+        interrupts.request(Interrupt::Timer);
+        assert_eq!(interrupts.pending(), None);
+
+        interrupts.write_enable(0xFF);
+        assert_eq!(interrupts.pending(), Some(Interrupt::Timer));
+        interrupts.acknowledge(Interrupt::Timer);
+        assert_eq!(interrupts.pending(), Some(Interrupt::Joypad));
+        assert_eq!(interrupts.read_flag(), 0xF0);
+    }
+
+    #[test]
+    fn unused_bits() {
+        let mut interrupts = Interrupts::default();
+        interrupts.write_flag(0x00);
+        assert_eq!(interrupts.read_flag(), 0xE0);
+        interrupts.write_flag(0xFF);
+        assert_eq!(interrupts.read_flag(), 0xFF);
+        interrupts.write_enable(0xE1);
+        assert_eq!(interrupts.read_enable(), 0xE1);
+        assert_eq!(interrupts.pending(), Some(Interrupt::VBlank));
+    }
+}
@@ -0,0 +1,116 @@
+use crate::gameboy::Model;
+
+pub const APU_START: u16 = 0xFF10;
+/// Last channel register, before NR52
+const CHANNELS_END: u16 = 0xFF25;
+pub const NR52: u16 = 0xFF26;
+pub const APU_END: u16 = 0xFF3F;
+const WAVE_RAM_START: u16 = 0xFF30;
+const WAVE_RAM_END: u16 = 0xFF3F;
+
+/// NRx4 registers, bit 7 triggers the channel
+const TRIGGERS: [u16; 4] = [0xFF14, 0xFF19, 0xFF1E, 0xFF23];
+const TRIGGER_BIT: u8 = 0b1000_0000;
+const POWER_BIT: u8 = 0b1000_0000;
+
+/// Bits reading as 1 in 0xFF10-0xFF25 : unused and write-only bits
+const READ_MASKS: [u8; 0x16] = [
+    0x80, 0x3F, 0x00, 0xFF, 0xBF, // NR10-NR14
+    0xFF, 0x3F, 0x00, 0xFF, 0xBF, // NR21-NR24
+    0x7F, 0xFF, 0x9F, 0xFF, 0xBF, // NR30-NR34
+    0xFF, 0xFF, 0x00, 0x00, 0xBF, // NR41-NR44
+    0x00, 0x00, // NR50-NR51
+];
+
+/// Sound registers, 0xFF10-0xFF3F.
+/// No sound is produced yet, triggered channels stay on until the APU is turned off.
+#[derive(Debug, Default)]
+pub struct Apu {
+    registers: [u8; 0x16],
+    wave_ram: [u8; 0x10],
+    power: bool,
+    /// Bits 3-0 of NR52 : channels on
+    channels: u8,
+}
+
+impl Apu {
+    /// State left by the boot ROM. The DMG one plays the boot sound on channel 1.
+    pub fn post_boot(model: Model) -> Self {
+        let mut apu = Self {
+            power: true,
+            ..Self::default()
+        };
+        let registers = [
+            (0xFF10, 0x80),
+            (0xFF11, 0xBF),
+            (0xFF12, 0xF3),
+            (0xFF13, 0xFF),
+            (0xFF14, 0xBF),
+            (0xFF16, 0x3F),
+            (0xFF17, 0x00),
+            (0xFF18, 0xFF),
+            (0xFF19, 0xBF),
+            (0xFF1A, 0x7F),
+            (0xFF1B, 0xFF),
+            (0xFF1C, 0x9F),
+            (0xFF1D, 0xFF),
+            (0xFF1E, 0xBF),
+            (0xFF20, 0xFF),
+            (0xFF21, 0x00),
+            (0xFF22, 0x00),
+            (0xFF23, 0xBF),
+            (0xFF24, 0x77),
+            (0xFF25, 0xF3),
+        ];
+        for &(address, value) in registers.iter() {
+            apu.registers[(address - APU_START) as usize] = value;
+        }
+        apu.channels = match model {
+            Model::Sgb | Model::Sgb2 => 0x00,
+            _ => 0x01,
+        };
+        apu
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            APU_START..=CHANNELS_END => {
+                let index = (address - APU_START) as usize;
+                self.registers[index] | READ_MASKS[index]
+            }
+            NR52 => {
+                let power = if self.power { POWER_BIT } else { 0 };
+                power | 0x70 | self.channels
+            }
+            WAVE_RAM_START..=WAVE_RAM_END => self.wave_ram[(address - WAVE_RAM_START) as usize],
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            // Registers are frozen while the APU is off
+            APU_START..=CHANNELS_END if !self.power => (),
+            APU_START..=CHANNELS_END => {
+                self.registers[(address - APU_START) as usize] = value;
+                if let Some(channel) = TRIGGERS.iter().position(|&trigger| trigger == address) {
+                    if value & TRIGGER_BIT != 0 {
+                        self.channels |= 1 << channel;
+                    }
+                }
+            }
+            NR52 => {
+                self.power = value & POWER_BIT != 0;
+                // Turning the APU off clears all its registers
+                if !self.power {
+                    self.registers = [0; 0x16];
+                    self.channels = 0;
+                }
+            }
+            WAVE_RAM_START..=WAVE_RAM_END => {
+                self.wave_ram[(address - WAVE_RAM_START) as usize] = value
+            }
+            _ => (),
+        }
+    }
+}
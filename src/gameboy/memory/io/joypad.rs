@@ -0,0 +1,110 @@
+pub const P1: u16 = 0xFF00;
+
+/// Bits 5-4 select the action or direction buttons
+const SELECT_MASK: u8 = 0b0011_0000;
+/// Selected when low
+const DIRECTION_SELECT: u8 = 0b0001_0000;
+const ACTION_SELECT: u8 = 0b0010_0000;
+
+/// Buttons, in the order of their bits on the action then direction lines
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Button {
+    A,
+    B,
+    Select,
+    Start,
+    Right,
+    Left,
+    Up,
+    Down,
+}
+
+/// Joypad register, 0xFF00
+#[derive(Debug)]
+pub struct Joypad {
+    select: u8,
+    /// Held buttons, the action ones in the lower nibble, the directions in the upper one
+    pressed: u8,
+}
+
+impl Default for Joypad {
+    fn default() -> Self {
+        Self {
+            select: SELECT_MASK,
+            pressed: 0,
+        }
+    }
+}
+
+impl Joypad {
+    pub fn read(&self) -> u8 {
+        // Bits 7-6 are unused, bits 3-0 are the buttons of the selected lines, low when held
+        0b1100_0000 | self.select | self.lines()
+    }
+
+    /// A button of the selected lines pulls its bit low
+    pub fn is_pressed(&self) -> bool {
+        self.lines() != 0x0F
+    }
+
+    pub fn write(&mut self, value: u8) {
+        self.select = value & SELECT_MASK;
+    }
+
+    /// Hold or release a button.
+    /// Return true when one of the selected lines goes low, which requests the joypad interrupt.
+    pub fn set_pressed(&mut self, button: Button, pressed: bool) -> bool {
+        let lines = self.lines();
+        let bit = 1 << button as u8;
+        if pressed {
+            self.pressed |= bit;
+        } else {
+            self.pressed &= !bit;
+        }
+        lines & !self.lines() != 0
+    }
+
+    fn lines(&self) -> u8 {
+        let mut held = 0;
+        if self.select & ACTION_SELECT == 0 {
+            held |= self.pressed & 0x0F;
+        }
+        if self.select & DIRECTION_SELECT == 0 {
+            held |= self.pressed >> 4;
+        }
+        !held & 0x0F
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn selected_lines() {
+        let mut joypad = Joypad::default();
+        joypad.set_pressed(Button::Start, true);
+        joypad.set_pressed(Button::Up, true);
+        assert_eq!(joypad.read(), 0xFF);
+        assert!(!joypad.is_pressed());
+        joypad.write(0x10);
+        assert_eq!(joypad.read(), 0xD7);
+        joypad.write(0x20);
+        assert_eq!(joypad.read(), 0xEB);
+        joypad.write(0x00);
+        assert_eq!(joypad.read(), 0xC3);
+        joypad.set_pressed(Button::Up, false);
+        assert_eq!(joypad.read(), 0xC7);
+    }
+
+    #[test]
+    fn interrupt_on_selected_press() {
+        let mut joypad = Joypad::default();
+        joypad.write(0x20);
+        assert!(!joypad.set_pressed(Button::A, true));
+        assert!(joypad.set_pressed(Button::Left, true));
+        // Already low
+        assert!(!joypad.set_pressed(Button::Left, true));
+        assert!(!joypad.set_pressed(Button::Left, false));
+    }
+}
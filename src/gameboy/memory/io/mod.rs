@@ -0,0 +1,168 @@
+/// Sound registers
+mod apu;
+/// Buttons
+mod joypad;
+/// LCD registers
+mod ppu;
+/// Serial port
+mod serial;
+/// Timer and divider
+mod timer;
+
+use super::interrupts::{Interrupt, Interrupts};
+use crate::gameboy::Model;
+use apu::{Apu, APU_END, APU_START};
+pub use joypad::Button;
+use joypad::{Joypad, P1};
+use ppu::{PpuRegisters, LCDC, WX};
+pub use serial::SerialSink;
+use serial::{Serial, SB, SC};
+use timer::{Timer, DIV, TAC};
+
+/// I/O registers, 0xFF00-0xFF7F.
+/// Each address is routed to the subsystem owning it, which applies its read masks and
+/// write side effects. Unmapped addresses read 0xFF and ignore writes.
+/// The OAM DMA, boot ROM and interrupt flag registers are handled by the bus.
+#[derive(Debug, Default)]
+pub struct IoRegisters {
+    joypad: Joypad,
+    serial: Serial,
+    timer: Timer,
+    apu: Apu,
+    ppu: PpuRegisters,
+}
+
+impl IoRegisters {
+    /// Registers as left by the boot ROM of the model
+    pub fn post_boot(model: Model) -> Self {
+        Self {
+            joypad: Joypad::default(),
+            serial: Serial::post_boot(),
+            timer: Timer::post_boot(model),
+            apu: Apu::post_boot(model),
+            ppu: PpuRegisters::post_boot(),
+        }
+    }
+
+    /// Advance the clocked subsystems by some T-cycles, requesting their interrupts
+    pub fn tick(&mut self, cycles: u32, interrupts: &mut Interrupts) {
+        if self.timer.tick(cycles) {
+            interrupts.request(Interrupt::Timer);
+        }
+        if self.serial.tick(cycles) {
+            interrupts.request(Interrupt::Serial);
+        }
+    }
+
+    pub fn set_serial_sink(&mut self, sink: SerialSink) {
+        self.serial.set_sink(sink);
+    }
+
+    pub fn reset_divider(&mut self) {
+        self.timer.write(DIV, 0x00);
+    }
+
+    pub fn is_button_pressed(&self) -> bool {
+        self.joypad.is_pressed()
+    }
+
+    /// Return true when the joypad interrupt is requested
+    pub fn set_button(&mut self, button: Button, pressed: bool) -> bool {
+        self.joypad.set_pressed(button, pressed)
+    }
+
+    pub fn ppu_mut(&mut self) -> &mut PpuRegisters {
+        &mut self.ppu
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            P1 => self.joypad.read(),
+            SB..=SC => self.serial.read(address),
+            DIV..=TAC => self.timer.read(address),
+            APU_START..=APU_END => self.apu.read(address),
+            LCDC..=WX => self.ppu.read(address),
+            _ => 0xFF,
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            P1 => self.joypad.write(value),
+            SB..=SC => self.serial.write(address, value),
+            DIV..=TAC => self.timer.write(address, value),
+            APU_START..=APU_END => self.apu.write(address, value),
+            LCDC..=WX => self.ppu.write(address, value),
+            _ => log::debug!("Ignored write to I/O {:04x} : {:02x}", address, value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unused_bits_read_as_one() {
+        let mut io = IoRegisters::default();
+        io.write(P1, 0x00);
+        assert_eq!(io.read(P1), 0xCF);
+        io.write(SC, 0x00);
+        assert_eq!(io.read(SC), 0x7E);
+        io.write(TAC, 0x00);
+        assert_eq!(io.read(TAC), 0xF8);
+        // NR11 : only the duty cycle can be read back
+        io.write(0xFF26, 0x80);
+        io.write(0xFF11, 0x00);
+        assert_eq!(io.read(0xFF11), 0x3F);
+        // Unmapped
+        io.write(0xFF03, 0x00);
+        assert_eq!(io.read(0xFF03), 0xFF);
+        assert_eq!(io.read(0xFF4D), 0xFF);
+    }
+
+    #[test]
+    fn div_write_resets() {
+        let mut io = IoRegisters::post_boot(Model::Dmg);
+        assert_eq!(io.read(DIV), 0xAB);
+        io.write(DIV, 0x42);
+        assert_eq!(io.read(DIV), 0x00);
+    }
+
+    #[test]
+    fn read_only_bits() {
+        let mut io = IoRegisters::default();
+        // LY belongs to the PPU
+        io.write(ppu::LY, 0x42);
+        assert_eq!(io.read(ppu::LY), 0x00);
+        io.ppu_mut().set_ly(0x42);
+        assert_eq!(io.read(ppu::LY), 0x42);
+
+        // Mode and coincidence bits of STAT
+        io.write(ppu::LYC, 0x42);
+        io.write(ppu::STAT, 0xFF);
+        assert_eq!(io.read(ppu::STAT), 0xFC);
+        io.ppu_mut().set_ly(0x00);
+        assert_eq!(io.read(ppu::STAT), 0xF8);
+    }
+
+    #[test]
+    fn apu_power() {
+        let mut io = IoRegisters::post_boot(Model::Dmg);
+        assert_eq!(io.read(apu::NR52), 0xF1);
+        io.write(0xFF30, 0x42);
+
+        io.write(apu::NR52, 0x00);
+        assert_eq!(io.read(apu::NR52), 0x70);
+        assert_eq!(io.read(0xFF24), 0x00);
+        // Frozen while off, except the wave RAM
+        io.write(0xFF24, 0x77);
+        assert_eq!(io.read(0xFF24), 0x00);
+        assert_eq!(io.read(0xFF30), 0x42);
+
+        // Triggering a channel turns it on
+        io.write(apu::NR52, 0x80);
+        io.write(0xFF23, 0x80);
+        assert_eq!(io.read(apu::NR52), 0xF8);
+    }
+}
@@ -0,0 +1,102 @@
+pub const LCDC: u16 = 0xFF40;
+pub const STAT: u16 = 0xFF41;
+pub const SCY: u16 = 0xFF42;
+pub const SCX: u16 = 0xFF43;
+pub const LY: u16 = 0xFF44;
+pub const LYC: u16 = 0xFF45;
+pub const BGP: u16 = 0xFF47;
+pub const OBP0: u16 = 0xFF48;
+pub const OBP1: u16 = 0xFF49;
+pub const WY: u16 = 0xFF4A;
+pub const WX: u16 = 0xFF4B;
+
+/// Interrupt selection bits of STAT, the others are read only
+const STAT_WRITE_MASK: u8 = 0b0111_1000;
+const STAT_COINCIDENCE_BIT: u8 = 0b0000_0100;
+/// LY read by the ROMs in the Gameboy Doctor reference logs
+const DOCTOR_LY: u8 = 0x90;
+
+/// LCD registers, 0xFF40-0xFF4B, except the OAM DMA
+#[derive(Debug, Default)]
+pub struct PpuRegisters {
+    lcdc: u8,
+    /// Writable part of STAT
+    stat: u8,
+    /// Current PPU mode, bits 1-0 of STAT
+    mode: u8,
+    scy: u8,
+    scx: u8,
+    /// Line being drawn, set by the PPU only
+    ly: u8,
+    lyc: u8,
+    bgp: u8,
+    obp0: u8,
+    obp1: u8,
+    wy: u8,
+    wx: u8,
+    /// LY always reads `DOCTOR_LY`, to compare traces with the Gameboy Doctor logs
+    stub_ly: bool,
+}
+
+impl PpuRegisters {
+    /// State left by the boot ROM, in VBlank
+    pub fn post_boot() -> Self {
+        Self {
+            lcdc: 0x91,
+            mode: 0x01,
+            bgp: 0xFC,
+            ..Self::default()
+        }
+    }
+
+    /// Update LY, as the PPU moves to the next line
+    pub fn set_ly(&mut self, line: u8) {
+        self.ly = line;
+    }
+
+    pub fn stub_ly(&mut self) {
+        self.stub_ly = true;
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            LCDC => self.lcdc,
+            STAT => {
+                let coincidence = if self.ly == self.lyc {
+                    STAT_COINCIDENCE_BIT
+                } else {
+                    0
+                };
+                0x80 | self.stat | coincidence | self.mode
+            }
+            SCY => self.scy,
+            SCX => self.scx,
+            LY if self.stub_ly => DOCTOR_LY,
+            LY => self.ly,
+            LYC => self.lyc,
+            BGP => self.bgp,
+            OBP0 => self.obp0,
+            OBP1 => self.obp1,
+            WY => self.wy,
+            WX => self.wx,
+            _ => unreachable!("PPU register out of range : {:04x}", address),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            LCDC => self.lcdc = value,
+            STAT => self.stat = value & STAT_WRITE_MASK,
+            SCY => self.scy = value,
+            SCX => self.scx = value,
+            LY => log::debug!("Ignored write to LY : {:02x}", value),
+            LYC => self.lyc = value,
+            BGP => self.bgp = value,
+            OBP0 => self.obp0 = value,
+            OBP1 => self.obp1 = value,
+            WY => self.wy = value,
+            WX => self.wx = value,
+            _ => unreachable!("PPU register out of range : {:04x}", address),
+        }
+    }
+}
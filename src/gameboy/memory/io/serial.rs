@@ -0,0 +1,166 @@
+use std::fmt;
+use std::io::Write;
+
+pub const SB: u16 = 0xFF01;
+pub const SC: u16 = 0xFF02;
+
+/// Only the transfer start and the clock select bits exist on DMG
+const SC_MASK: u8 = 0b1000_0001;
+const SC_TRANSFER: u8 = 0b1000_0000;
+const SC_INTERNAL_CLOCK: u8 = 0b0000_0001;
+/// T-cycles per bit with the internal 8192 Hz clock
+const BIT_PERIOD: u32 = 512;
+
+/// Receives the bytes sent over the serial port
+pub type SerialSink = Box<dyn Write + Send + Sync>;
+
+/// Serial port registers, 0xFF01-0xFF02.
+/// No peer is connected: the bits received are all ones, and a transfer on the external
+/// clock never completes.
+#[derive(Default)]
+pub struct Serial {
+    /// Byte to send, replaced by the received one
+    data: u8,
+    control: u8,
+    /// Byte being sent, given to the sink once the transfer completes
+    sending: u8,
+    /// Bits left to shift in the current transfer
+    bits: u8,
+    /// T-cycles not making a full bit period yet
+    cycles: u32,
+    sink: Option<SerialSink>,
+}
+
+impl fmt::Debug for Serial {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Serial")
+            .field("data", &self.data)
+            .field("control", &self.control)
+            .field("bits", &self.bits)
+            .field("sink", &self.sink.is_some())
+            .finish()
+    }
+}
+
+impl Serial {
+    /// State left by the boot ROM
+    pub fn post_boot() -> Self {
+        Self {
+            data: 0x00,
+            control: 0x7E,
+            ..Self::default()
+        }
+    }
+
+    pub fn set_sink(&mut self, sink: SerialSink) {
+        self.sink = Some(sink);
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            SB => self.data,
+            SC => self.control | !SC_MASK,
+            _ => unreachable!("Serial register out of range : {:04x}", address),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            SB => self.data = value,
+            SC => {
+                self.control = value & SC_MASK;
+                // Starting again restarts the transfer, clearing the bit aborts it
+                if self.control & SC_TRANSFER != 0 {
+                    self.sending = self.data;
+                    self.bits = 8;
+                    self.cycles = 0;
+                } else {
+                    self.bits = 0;
+                }
+            }
+            _ => unreachable!("Serial register out of range : {:04x}", address),
+        }
+    }
+
+    /// Advance the transfer on the internal clock by some T-cycles.
+    /// Return true when the serial interrupt is requested.
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        if self.bits == 0 || self.control & SC_INTERNAL_CLOCK == 0 {
+            return false;
+        }
+        self.cycles += cycles;
+        while self.cycles >= BIT_PERIOD && self.bits > 0 {
+            self.cycles -= BIT_PERIOD;
+            self.bits -= 1;
+            // Out on the most significant bit, in from the disconnected line
+            self.data = self.data << 1 | 1;
+        }
+        if self.bits > 0 {
+            return false;
+        }
+        self.control &= !SC_TRANSFER;
+        self.send(self.sending);
+        true
+    }
+
+    fn send(&mut self, byte: u8) {
+        if let Some(sink) = self.sink.as_mut() {
+            if let Err(e) = sink.write_all(&[byte]).and_then(|_| sink.flush()) {
+                log::error!("Failed to write the serial output, dropping it : {}", e);
+                self.sink = None;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::test_output::SharedOutput;
+
+    #[test]
+    fn internal_clock_transfer() {
+        let mut serial = Serial::default();
+        let output = SharedOutput::default();
+        serial.set_sink(Box::new(output.clone()));
+        serial.write(SB, b'O');
+        serial.write(SC, 0x81);
+        assert!(!serial.tick(BIT_PERIOD * 8 - 4));
+        assert_eq!(serial.read(SC), 0xFF);
+        assert!(output.bytes().is_empty());
+        assert!(serial.tick(4));
+        assert_eq!(serial.read(SC), 0x7F);
+        assert_eq!(serial.read(SB), 0xFF);
+        assert_eq!(output.bytes(), b"O");
+        // Done
+        assert!(!serial.tick(BIT_PERIOD * 8));
+    }
+
+    #[test]
+    fn bits_shift_out() {
+        let mut serial = Serial::default();
+        serial.write(SB, 0b1010_0000);
+        serial.write(SC, 0x81);
+        serial.tick(BIT_PERIOD * 3);
+        assert_eq!(serial.read(SB), 0b0000_0111);
+    }
+
+    #[test]
+    fn external_clock_never_completes() {
+        let mut serial = Serial::default();
+        serial.write(SB, b'K');
+        serial.write(SC, 0x80);
+        assert!(!serial.tick(BIT_PERIOD * 100));
+        assert_eq!(serial.read(SC), 0xFE);
+        assert_eq!(serial.read(SB), b'K');
+    }
+
+    #[test]
+    fn abort() {
+        let mut serial = Serial::default();
+        serial.write(SC, 0x81);
+        serial.tick(BIT_PERIOD);
+        serial.write(SC, 0x01);
+        assert!(!serial.tick(BIT_PERIOD * 8));
+    }
+}
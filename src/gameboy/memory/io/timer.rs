@@ -0,0 +1,238 @@
+use crate::gameboy::Model;
+
+pub const DIV: u16 = 0xFF04;
+pub const TIMA: u16 = 0xFF05;
+pub const TMA: u16 = 0xFF06;
+pub const TAC: u16 = 0xFF07;
+
+/// Enable and clock select bits
+const TAC_MASK: u8 = 0b0000_0111;
+const TAC_ENABLE: u8 = 0b0000_0100;
+/// T-cycles in a M-cycle, the timer is updated once per M-cycle
+const M_CYCLE: u32 = 4;
+
+/// Timer registers, 0xFF04-0xFF07
+#[derive(Debug, Default)]
+pub struct Timer {
+    /// Internal 16 bits counter, DIV is its upper byte
+    divider: u16,
+    /// Timer counter
+    tima: u8,
+    /// Timer modulo, loaded in TIMA on overflow
+    tma: u8,
+    /// Timer control
+    tac: u8,
+    /// TIMA overflowed during the last M-cycle, it reads 0 until reloaded
+    overflow: bool,
+    /// TIMA was reloaded from TMA during the last M-cycle
+    reloading: bool,
+    /// T-cycles not making a full M-cycle yet
+    cycles: u32,
+}
+
+impl Timer {
+    /// State left by the boot ROM
+    pub fn post_boot(model: Model) -> Self {
+        let div: u8 = match model {
+            Model::Dmg0 => 0x18,
+            _ => 0xAB,
+        };
+        Self {
+            divider: (div as u16) << 8,
+            ..Self::default()
+        }
+    }
+
+    pub fn read(&self, address: u16) -> u8 {
+        match address {
+            DIV => (self.divider >> 8) as u8,
+            TIMA => self.tima,
+            TMA => self.tma,
+            TAC => self.tac | !TAC_MASK,
+            _ => unreachable!("Timer register out of range : {:04x}", address),
+        }
+    }
+
+    pub fn write(&mut self, address: u16, value: u8) {
+        match address {
+            // Any write resets the whole counter, which can be seen as a falling edge
+            DIV => {
+                let signal = self.signal();
+                self.divider = 0;
+                self.increment_on_falling_edge(signal);
+            }
+            // Writing during the overflow cycle cancels the reload,
+            // writing during the reload cycle is ignored
+            TIMA => {
+                if !self.reloading {
+                    self.tima = value;
+                    self.overflow = false;
+                }
+            }
+            // Also loaded in TIMA during the reload cycle
+            TMA => {
+                self.tma = value;
+                if self.reloading {
+                    self.tima = value;
+                }
+            }
+            // Disabling the timer or selecting another bit can be seen as a falling edge
+            TAC => {
+                let signal = self.signal();
+                self.tac = value & TAC_MASK;
+                self.increment_on_falling_edge(signal);
+            }
+            _ => unreachable!("Timer register out of range : {:04x}", address),
+        }
+    }
+
+    /// Advance the timer by some T-cycles.
+    /// Return true when the timer interrupt is requested.
+    pub fn tick(&mut self, cycles: u32) -> bool {
+        self.cycles += cycles;
+        let mut interrupt = false;
+        while self.cycles >= M_CYCLE {
+            self.cycles -= M_CYCLE;
+            interrupt |= self.step();
+        }
+        interrupt
+    }
+
+    /// Advance the timer by one M-cycle
+    fn step(&mut self) -> bool {
+        self.reloading = false;
+        let interrupt = self.overflow;
+        if self.overflow {
+            self.overflow = false;
+            self.reloading = true;
+            self.tima = self.tma;
+        }
+        let signal = self.signal();
+        self.divider = self.divider.wrapping_add(M_CYCLE as u16);
+        self.increment_on_falling_edge(signal);
+        interrupt
+    }
+
+    /// Bit of the divider selected by TAC, when the timer is enabled
+    fn signal(&self) -> bool {
+        let bit = match self.tac & 0b11 {
+            0b00 => 9,
+            0b01 => 3,
+            0b10 => 5,
+            _ => 7,
+        };
+        self.tac & TAC_ENABLE != 0 && (self.divider >> bit) & 1 != 0
+    }
+
+    /// Increment TIMA when the signal went from 1 to 0
+    fn increment_on_falling_edge(&mut self, previous_signal: bool) {
+        if !previous_signal || self.signal() {
+            return;
+        }
+        let (tima, overflow) = self.tima.overflowing_add(1);
+        self.tima = tima;
+        self.overflow |= overflow;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn divider() {
+        let mut timer = Timer::default();
+        timer.tick(255);
+        assert_eq!(timer.read(DIV), 0x00);
+        timer.tick(1);
+        assert_eq!(timer.read(DIV), 0x01);
+        timer.tick(256 * 0xFF);
+        assert_eq!(timer.read(DIV), 0x00);
+    }
+
+    #[test]
+    fn clock_select() {
+        for &(tac, period) in [(0x04, 1024), (0x05, 16), (0x06, 64), (0x07, 256)].iter() {
+            let mut timer = Timer::default();
+            timer.write(TAC, tac);
+            timer.tick(period - 4);
+            assert_eq!(timer.read(TIMA), 0, "TAC {:02x}", tac);
+            timer.tick(4);
+            assert_eq!(timer.read(TIMA), 1, "TAC {:02x}", tac);
+            timer.tick(period * 10);
+            assert_eq!(timer.read(TIMA), 11, "TAC {:02x}", tac);
+        }
+
+        // Disabled
+        let mut timer = Timer::default();
+        timer.write(TAC, 0x01);
+        timer.tick(1024);
+        assert_eq!(timer.read(TIMA), 0);
+    }
+
+    #[test]
+    fn overflow() {
+        let mut timer = Timer::default();
+        timer.write(TAC, 0x05);
+        timer.write(TMA, 0x42);
+        timer.write(TIMA, 0xFF);
+        assert!(!timer.tick(16));
+        // Reads 0 for one M-cycle
+        assert_eq!(timer.read(TIMA), 0x00);
+        assert!(timer.tick(4));
+        assert_eq!(timer.read(TIMA), 0x42);
+        assert!(!timer.tick(4));
+    }
+
+    #[test]
+    fn overflow_writes() {
+        let mut timer = Timer::default();
+        timer.write(TAC, 0x05);
+        timer.write(TIMA, 0xFF);
+        timer.tick(16);
+        // Writing TIMA during the overflow cycle cancels the reload and the interrupt
+        timer.write(TIMA, 0x10);
+        assert!(!timer.tick(4));
+        assert_eq!(timer.read(TIMA), 0x10);
+
+        let mut timer = Timer::default();
+        timer.write(TAC, 0x05);
+        timer.write(TIMA, 0xFF);
+        timer.tick(20);
+        // Writing TIMA during the reload cycle is ignored, TMA goes through
+        timer.write(TIMA, 0x10);
+        assert_eq!(timer.read(TIMA), 0x00);
+        timer.write(TMA, 0x20);
+        assert_eq!(timer.read(TIMA), 0x20);
+    }
+
+    #[test]
+    fn div_write_glitch() {
+        let mut timer = Timer::default();
+        timer.write(TAC, 0x05);
+        // Bit 3 of the divider is set
+        timer.tick(8);
+        assert_eq!(timer.read(TIMA), 0);
+        timer.write(DIV, 0x00);
+        assert_eq!(timer.read(TIMA), 1);
+        // Bit 3 is clear, no increment
+        timer.write(DIV, 0x00);
+        assert_eq!(timer.read(TIMA), 1);
+    }
+
+    #[test]
+    fn tac_write_glitch() {
+        let mut timer = Timer::default();
+        timer.write(TAC, 0x05);
+        timer.tick(8);
+        // Disabling the timer while the bit is set
+        timer.write(TAC, 0x01);
+        assert_eq!(timer.read(TIMA), 1);
+
+        // Selecting a bit which is clear
+        timer.write(TAC, 0x05);
+        timer.tick(8);
+        timer.write(TAC, 0x04);
+        assert_eq!(timer.read(TIMA), 2);
+    }
+}
@@ -24,17 +24,9 @@ pub trait Memory {
         self.buffer_as_mut()[address as usize] = content;
     }
 
-    /// write word
-    fn write_word(&mut self, address: u16, content: u16) {
-        let address = Self::local_address(address);
-        // lower part
-        self.buffer_as_mut()[address as usize] = (content ^ 0xFF) as u8;
-        // higher part
-        self.buffer_as_mut()[(address + 1) as usize] = (content >> 8) as u8;
-    }
-
     /// transmute global address into local address
     fn local_address(address: u16) -> u16 {
+        debug_assert!(Self::range().contains(&address));
         address - Self::start()
     }
 
@@ -45,15 +37,6 @@ pub trait Memory {
 
         self.buffer()[address as usize]
     }
-
-    /// read word = 2 bytes
-    fn read_word(&self, address: u16) -> u16 {
-        let address = Self::local_address(address);
-
-        // beware of the endianess
-        (self.buffer()[address as usize] as u16)
-            | ((self.buffer()[(address + 1) as usize] as u16) << 8)
-    }
 }
 
 #[cfg(test)]
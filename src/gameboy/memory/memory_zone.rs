@@ -1,64 +1,49 @@
 use super::{
     memory_behavior::Memory, BANK_0_END, BANK_0_SIZE, BANK_0_START, BANK_1_END, BANK_1_SIZE,
-    BANK_1_START, ECHO_RAM_END, ECHO_RAM_SIZE, ECHO_RAM_START, EXT_RAM_END, EXT_RAM_SIZE,
-    EXT_RAM_START, HIGH_RAM_END, HIGH_RAM_SIZE, HIGH_RAM_START, INTERRUPTS_REGISTER,
-    INTERRUPTS_REGISTER_SIZE, IO_REGISTER_END, IO_REGISTER_SIZE, IO_REGISTER_START, ROM_END,
-    ROM_SIZE, ROM_START, SPRITE_TABLE_END, SPRITE_TABLE_SIZE, SPRITE_TABLE_START,
+    BANK_1_START, BOOT_SEQUENCE_END, BOOT_SEQUENCE_SIZE, BOOT_SEQUENCE_START, HIGH_RAM_END,
+    HIGH_RAM_SIZE, HIGH_RAM_START, SPRITE_TABLE_END, SPRITE_TABLE_SIZE, SPRITE_TABLE_START,
 };
 
-/// From 0x0000 to 0x7FFF
-/// Read only memory : boot sequence + cartridge
+/// From 0x0000 to 0x00FF
+/// Boot sequence, mapped over the cartridge ROM until a nonzero write to 0xFF50
 #[derive(Debug)]
-pub struct ReadOnlyMemory {
-    buffer: [u8; ROM_SIZE],
+pub struct BootRom {
+    buffer: [u8; BOOT_SEQUENCE_SIZE],
+    mapped: bool,
 }
 
-impl Default for ReadOnlyMemory {
+impl Default for BootRom {
     fn default() -> Self {
         Self {
-            buffer: [0u8; ROM_SIZE],
+            buffer: [0u8; BOOT_SEQUENCE_SIZE],
+            mapped: false,
         }
     }
 }
 
-impl Memory for ReadOnlyMemory {
-    fn start() -> u16 {
-        ROM_START
-    }
-
-    fn end() -> u16 {
-        ROM_END
+impl BootRom {
+    pub fn is_mapped(&self) -> bool {
+        self.mapped
     }
 
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
+    /// Map the boot sequence, hiding the beginning of the cartridge
+    pub fn map(&mut self) {
+        self.mapped = true;
     }
 
-    fn buffer_as_mut(&mut self) -> &mut [u8] {
-        &mut self.buffer
+    /// Reveal the cartridge. Only a reset maps the boot sequence again.
+    pub fn unmap(&mut self) {
+        self.mapped = false;
     }
 }
 
-#[derive(Debug)]
-pub struct ExternalRam {
-    buffer: [u8; EXT_RAM_SIZE],
-}
-
-impl Default for ExternalRam {
-    fn default() -> Self {
-        Self {
-            buffer: [0u8; EXT_RAM_SIZE],
-        }
-    }
-}
-
-impl Memory for ExternalRam {
+impl Memory for BootRom {
     fn start() -> u16 {
-        EXT_RAM_START
+        BOOT_SEQUENCE_START
     }
 
     fn end() -> u16 {
-        EXT_RAM_END
+        BOOT_SEQUENCE_END
     }
 
     fn buffer(&self) -> &[u8] {
@@ -132,37 +117,6 @@ impl Memory for Bank0 {
     }
 }
 
-#[derive(Debug)]
-pub struct EchoRam {
-    buffer: [u8; ECHO_RAM_SIZE],
-}
-
-impl Default for EchoRam {
-    fn default() -> Self {
-        Self {
-            buffer: [0u8; ECHO_RAM_SIZE],
-        }
-    }
-}
-
-impl Memory for EchoRam {
-    fn start() -> u16 {
-        ECHO_RAM_START
-    }
-
-    fn end() -> u16 {
-        ECHO_RAM_END
-    }
-
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
-    }
-
-    fn buffer_as_mut(&mut self) -> &mut [u8] {
-        &mut self.buffer
-    }
-}
-
 #[derive(Debug)]
 pub struct SpriteAttributeTable {
     buffer: [u8; SPRITE_TABLE_SIZE],
@@ -194,37 +148,6 @@ impl Memory for SpriteAttributeTable {
     }
 }
 
-#[derive(Debug)]
-pub struct IoRegister {
-    buffer: [u8; IO_REGISTER_SIZE],
-}
-
-impl Default for IoRegister {
-    fn default() -> Self {
-        Self {
-            buffer: [0u8; IO_REGISTER_SIZE],
-        }
-    }
-}
-
-impl Memory for IoRegister {
-    fn start() -> u16 {
-        IO_REGISTER_START
-    }
-
-    fn end() -> u16 {
-        IO_REGISTER_END
-    }
-
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
-    }
-
-    fn buffer_as_mut(&mut self) -> &mut [u8] {
-        &mut self.buffer
-    }
-}
-
 #[derive(Debug)]
 pub struct HighRam {
     buffer: [u8; HIGH_RAM_SIZE],
@@ -255,34 +178,3 @@ impl Memory for HighRam {
         &mut self.buffer
     }
 }
-
-#[derive(Debug)]
-pub struct InterruptsRegister {
-    buffer: [u8; INTERRUPTS_REGISTER_SIZE],
-}
-
-impl Default for InterruptsRegister {
-    fn default() -> Self {
-        Self {
-            buffer: [0u8; INTERRUPTS_REGISTER_SIZE],
-        }
-    }
-}
-
-impl Memory for InterruptsRegister {
-    fn start() -> u16 {
-        INTERRUPTS_REGISTER
-    }
-
-    fn end() -> u16 {
-        INTERRUPTS_REGISTER
-    }
-
-    fn buffer(&self) -> &[u8] {
-        &self.buffer
-    }
-
-    fn buffer_as_mut(&mut self) -> &mut [u8] {
-        &mut self.buffer
-    }
-}
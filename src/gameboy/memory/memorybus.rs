@@ -1,20 +1,24 @@
-use std::fs::File;
-use std::io::Read;
+use std::fs;
+use std::path::Path;
 
+use crate::gameboy::cartridge::{Cartridge, CartridgeMemory};
 use crate::gameboy::memory::memory_behavior::Memory;
 use crate::gameboy::memory::{
-    BANK_0_END, BANK_0_START, BANK_1_END, BANK_1_START, ECHO_RAM_END, ECHO_RAM_START, HIGH_RAM_END,
-    HIGH_RAM_START, INTERRUPTS_REGISTER, IO_REGISTER_END, IO_REGISTER_START, SPRITE_TABLE_END,
-    SPRITE_TABLE_START, VRAM_END, VRAM_START,
+    BANK_0_END, BANK_0_START, BANK_1_END, BANK_1_START, ECHO_RAM_END, ECHO_RAM_OFFSET,
+    ECHO_RAM_START, HIGH_RAM_END, HIGH_RAM_START, INTERRUPTS_REGISTER, IO_REGISTER_END,
+    IO_REGISTER_START, SPRITE_TABLE_END, SPRITE_TABLE_START, UNUSABLE_END, UNUSABLE_START,
+    VRAM_END, VRAM_START,
 };
+use crate::gameboy::{Error, GbResult, Model};
 
-use super::memory_zone::{
-    Bank0, Bank1, EchoRam, ExternalRam, HighRam, InterruptsRegister, IoRegister, ReadOnlyMemory,
-    SpriteAttributeTable,
-};
+use super::memory_zone::{Bank0, Bank1, BootRom, HighRam, SpriteAttributeTable};
 use super::{
-    vram::VideoRam, BOOT_SEQUENCE_PATH, BOOT_SEQUENCE_SIZE, EXT_RAM_END, EXT_RAM_START, ROM_END,
-    ROM_START,
+    dma::OamDma,
+    interrupts::{Interrupt, Interrupts, IF},
+    io::{Button, IoRegisters, SerialSink},
+    vram::VideoRam,
+    BOOT_SEQUENCE_DISABLE, BOOT_SEQUENCE_END, BOOT_SEQUENCE_SIZE, BOOT_SEQUENCE_START, EXT_RAM_END,
+    EXT_RAM_START, OAM_DMA_REGISTER, ROM_END, ROM_START,
 };
 
 // CHECKME
@@ -25,84 +29,96 @@ use std::sync::RwLock;
 /// Components can communicate via this bus
 /// TODO use RwLock on each component when getting multithreaded
 pub struct MemoryBus {
-    /// ROM : boosequence + cartridge game
-    read_only_memory: RwLock<ReadOnlyMemory>,
+    /// Boot sequence, mapped over the beginning of the cartridge ROM
+    boot_rom: RwLock<BootRom>,
+    /// ROM and RAM of the cartridge, behind its memory bank controller
+    cartridge: RwLock<CartridgeMemory>,
     /// Buffer for display
     video_ram: RwLock<VideoRam>,
-    /// RAM brought by the cartridge
-    external_ram: RwLock<ExternalRam>,
     /// Work RAM. Does not exist in GB
     bank_0: RwLock<Bank0>,
     /// Work RAM. Presents with GB and CGB
     bank_1: RwLock<Bank1>,
-    /// Echo RAM. Usually not used
-    echo_ram: RwLock<EchoRam>,
     /// Sprite attribute table
     sprite_attribute_table: RwLock<SpriteAttributeTable>,
     /// IO register to store inputs, sound etc
-    io_register: RwLock<IoRegister>,
+    io_register: RwLock<IoRegisters>,
     /// Yet an other RAM
     high_ram: RwLock<HighRam>,
-    /// Interrupt register
-    interrupt_register: RwLock<InterruptsRegister>,
+    /// Copy to the sprite attribute table
+    oam_dma: RwLock<OamDma>,
+    /// Interrupt controller, IF and IE
+    interrupts: RwLock<Interrupts>,
 }
 
 impl MemoryBus {
-    /// Load boot only
-    pub fn new() -> Result<Self, String> {
+    /// Load cartridge, without boot sequence
+    pub fn load(cartridge: &Cartridge) -> Self {
         let memory_bus = MemoryBus::default();
-        memory_bus.load_boot()?;
-        Ok(memory_bus)
+        memory_bus.load_cartridge(cartridge);
+        memory_bus
     }
 
-    /// Load boot and cartride
-    pub fn load(rom_path: &str) -> Result<Self, String> {
-        let memory_bus = Self::new()?;
-        memory_bus.load_cartridge(rom_path)?;
-        Ok(memory_bus)
+    /// Advance the components driven by the bus clock, by some T-cycles
+    pub fn tick(&self, cycles: u32) {
+        self.io_register
+            .write()
+            .unwrap()
+            .tick(cycles, &mut self.interrupts.write().unwrap());
+        let (source, offsets) = {
+            let oam_dma = &mut self.oam_dma.write().unwrap();
+            (oam_dma.source(), oam_dma.tick(cycles))
+        };
+        for offset in offsets {
+            let mut source = source + offset;
+            // Sources above the work RAM read its mirror
+            if source >= ECHO_RAM_START {
+                source -= ECHO_RAM_OFFSET;
+            }
+            let value = self.read_byte_unblocked(source);
+            self.sprite_attribute_table
+                .write()
+                .unwrap()
+                .write_byte(SPRITE_TABLE_START + offset, value);
+        }
+    }
+
+    /// During OAM DMA, the external and video buses conflict with the transfer.
+    /// The CPU only reaches the I/O registers, the high RAM and IE.
+    fn is_blocked(&self, address: u16) -> bool {
+        address < IO_REGISTER_START && self.oam_dma.read().unwrap().is_active()
     }
 
     pub fn read_byte(&self, address: u16) -> u8 {
+        if self.is_blocked(address) {
+            return 0xFF;
+        }
+        self.read_byte_unblocked(address)
+    }
+
+    /// Read, ignoring the bus conflicts
+    fn read_byte_unblocked(&self, address: u16) -> u8 {
         match address {
-            ROM_START..=ROM_END => self.read_only_memory.read().unwrap().read_byte(address),
+            BOOT_SEQUENCE_START..=BOOT_SEQUENCE_END
+                if self.boot_rom.read().unwrap().is_mapped() =>
+            {
+                self.boot_rom.read().unwrap().read_byte(address)
+            }
+            ROM_START..=ROM_END => self.cartridge.read().unwrap().read_rom(address),
             VRAM_START..=VRAM_END => self.video_ram.read().unwrap().read_byte(address),
-            EXT_RAM_START..=EXT_RAM_END => self.external_ram.read().unwrap().read_byte(address),
+            EXT_RAM_START..=EXT_RAM_END => self.cartridge.read().unwrap().read_ram(address),
             BANK_0_START..=BANK_0_END => self.bank_0.read().unwrap().read_byte(address),
             BANK_1_START..=BANK_1_END => self.bank_1.read().unwrap().read_byte(address),
-            ECHO_RAM_START..=ECHO_RAM_END => self.echo_ram.read().unwrap().read_byte(address),
+            ECHO_RAM_START..=ECHO_RAM_END => self.read_byte_unblocked(address - ECHO_RAM_OFFSET),
             SPRITE_TABLE_START..=SPRITE_TABLE_END => self
                 .sprite_attribute_table
                 .read()
                 .unwrap()
                 .read_byte(address),
-            IO_REGISTER_START..=IO_REGISTER_END => {
-                self.io_register.read().unwrap().read_byte(address)
-            }
+            IO_REGISTER_START..=IO_REGISTER_END => self.read_io(address),
             HIGH_RAM_START..=HIGH_RAM_END => self.high_ram.read().unwrap().read_byte(address),
-            INTERRUPTS_REGISTER => self.interrupt_register.read().unwrap().read_byte(address),
-            _ => panic!("Reading address {:04x} not handled", address),
-        }
-    }
-
-    pub fn read_word(&self, address: u16) -> u16 {
-        match address {
-            ROM_START..=ROM_END => self.read_only_memory.read().unwrap().read_word(address),
-            VRAM_START..=VRAM_END => self.video_ram.read().unwrap().read_word(address),
-            EXT_RAM_START..=EXT_RAM_END => self.external_ram.read().unwrap().read_word(address),
-            BANK_0_START..=BANK_0_END => self.bank_0.read().unwrap().read_word(address),
-            BANK_1_START..=BANK_1_END => self.bank_1.read().unwrap().read_word(address),
-            ECHO_RAM_START..=ECHO_RAM_END => self.echo_ram.read().unwrap().read_word(address),
-            SPRITE_TABLE_START..=SPRITE_TABLE_END => self
-                .sprite_attribute_table
-                .read()
-                .unwrap()
-                .read_word(address),
-            IO_REGISTER_START..=IO_REGISTER_END => {
-                self.io_register.read().unwrap().read_word(address)
-            }
-            HIGH_RAM_START..=HIGH_RAM_END => self.high_ram.read().unwrap().read_word(address),
-            INTERRUPTS_REGISTER => self.interrupt_register.read().unwrap().read_word(address),
-            _ => panic!("Writing address {:04x} not handled", address),
+            UNUSABLE_START..=UNUSABLE_END => 0x00,
+            INTERRUPTS_REGISTER => self.interrupts.read().unwrap().read_enable(),
         }
     }
 
@@ -115,113 +131,377 @@ impl MemoryBus {
         self.video_ram.read().unwrap().clone()
     }
 
+    /// Read an I/O register, 0xFF00-0xFF7F.
+    /// Components use it to read their registers regardless of the CPU bus conflicts.
+    pub fn read_io(&self, address: u16) -> u8 {
+        match address {
+            OAM_DMA_REGISTER => (self.oam_dma.read().unwrap().source() >> 8) as u8,
+            IF => self.interrupts.read().unwrap().read_flag(),
+            // Write only
+            BOOT_SEQUENCE_DISABLE => 0xFF,
+            _ => self.io_register.read().unwrap().read(address),
+        }
+    }
+
+    fn write_io(&self, address: u16, value: u8) {
+        match address {
+            OAM_DMA_REGISTER => self.oam_dma.write().unwrap().start(value),
+            IF => self.interrupts.write().unwrap().write_flag(value),
+            BOOT_SEQUENCE_DISABLE => {
+                if value != 0 {
+                    self.boot_rom.write().unwrap().unmap();
+                }
+            }
+            _ => self.io_register.write().unwrap().write(address, value),
+        }
+    }
+
+    /// Set the bit of the interrupt in IF
+    pub fn request_interrupt(&self, interrupt: Interrupt) {
+        self.interrupts.write().unwrap().request(interrupt);
+    }
+
+    /// Requested and enabled interrupt with the highest priority
+    pub fn pending_interrupt(&self) -> Option<Interrupt> {
+        self.interrupts.read().unwrap().pending()
+    }
+
+    /// Clear the bit of the interrupt in IF
+    pub fn acknowledge_interrupt(&self, interrupt: Interrupt) {
+        self.interrupts.write().unwrap().acknowledge(interrupt);
+    }
+
+    /// Reset DIV, as STOP does
+    pub fn reset_divider(&self) {
+        self.io_register.write().unwrap().reset_divider();
+    }
+
+    /// Any button of the selected lines held down, which wakes up a stopped CPU
+    pub fn is_button_pressed(&self) -> bool {
+        self.io_register.read().unwrap().is_button_pressed()
+    }
+
+    /// Hold or release a button, requesting the joypad interrupt on a selected line
+    pub fn set_button(&self, button: Button, pressed: bool) {
+        if self
+            .io_register
+            .write()
+            .unwrap()
+            .set_button(button, pressed)
+        {
+            self.request_interrupt(Interrupt::Joypad);
+        }
+    }
+
+    /// Collect the bytes sent over the serial port
+    pub fn set_serial_sink(&self, sink: SerialSink) {
+        self.io_register.write().unwrap().set_serial_sink(sink);
+    }
+
+    /// LY always reads 0x90, as in the Gameboy Doctor reference logs
+    pub fn stub_ly(&self) {
+        self.io_register.write().unwrap().ppu_mut().stub_ly();
+    }
+
+    /// Update LY, which is read only for the CPU
+    pub fn set_ly(&self, line: u8) {
+        self.io_register.write().unwrap().ppu_mut().set_ly(line);
+    }
+
     /// write byte to memory
     pub fn write_byte(&self, address: u16, value: u8) {
+        if self.is_blocked(address) {
+            log::debug!("Write to {:04x} blocked by OAM DMA", address);
+            return;
+        }
         match address {
-            ROM_START..=ROM_END => self
-                .read_only_memory
-                .write()
-                .unwrap()
-                .write_byte(address, value),
+            // Writing to the ROM sets the memory bank controller registers
+            ROM_START..=ROM_END => self.cartridge.write().unwrap().write_rom(address, value),
             VRAM_START..=VRAM_END => self.video_ram.write().unwrap().write_byte(address, value),
-            EXT_RAM_START..=EXT_RAM_END => self
-                .external_ram
-                .write()
-                .unwrap()
-                .write_byte(address, value),
+            EXT_RAM_START..=EXT_RAM_END => {
+                self.cartridge.write().unwrap().write_ram(address, value)
+            }
             BANK_0_START..=BANK_0_END => self.bank_0.write().unwrap().write_byte(address, value),
             BANK_1_START..=BANK_1_END => self.bank_1.write().unwrap().write_byte(address, value),
-            ECHO_RAM_START..=ECHO_RAM_END => {
-                self.echo_ram.write().unwrap().write_byte(address, value)
-            }
+            ECHO_RAM_START..=ECHO_RAM_END => self.write_byte(address - ECHO_RAM_OFFSET, value),
             SPRITE_TABLE_START..=SPRITE_TABLE_END => self
                 .sprite_attribute_table
                 .write()
                 .unwrap()
                 .write_byte(address, value),
-            IO_REGISTER_START..=IO_REGISTER_END => {
-                self.io_register.write().unwrap().write_byte(address, value)
-            }
+            IO_REGISTER_START..=IO_REGISTER_END => self.write_io(address, value),
             HIGH_RAM_START..=HIGH_RAM_END => {
                 self.high_ram.write().unwrap().write_byte(address, value)
             }
-            INTERRUPTS_REGISTER => self
-                .interrupt_register
-                .write()
-                .unwrap()
-                .write_byte(address, value),
-            _ => panic!("Address {} not handled", address),
+            UNUSABLE_START..=UNUSABLE_END => {
+                log::debug!("Ignored write to unusable {:04x} : {:02x}", address, value)
+            }
+            INTERRUPTS_REGISTER => self.interrupts.write().unwrap().write_enable(value),
         };
     }
 
-    /// write word to memory in the proper subspace
-    pub fn write_word(&self, address: u16, value: u16) {
-        match address {
-            ROM_START..=ROM_END => self
-                .read_only_memory
-                .write()
-                .unwrap()
-                .write_word(address, value),
-            VRAM_START..=VRAM_END => self.video_ram.write().unwrap().write_word(address, value),
-            EXT_RAM_START..=EXT_RAM_END => self
-                .external_ram
-                .write()
-                .unwrap()
-                .write_word(address, value),
-            BANK_0_START..=BANK_0_END => self.bank_0.write().unwrap().write_word(address, value),
-            BANK_1_START..=BANK_1_END => self.bank_1.write().unwrap().write_word(address, value),
-            ECHO_RAM_START..=ECHO_RAM_END => {
-                self.echo_ram.write().unwrap().write_word(address, value)
-            }
-            SPRITE_TABLE_START..=SPRITE_TABLE_END => self
-                .sprite_attribute_table
-                .write()
-                .unwrap()
-                .write_word(address, value),
-            IO_REGISTER_START..=IO_REGISTER_END => {
-                self.io_register.write().unwrap().write_word(address, value)
-            }
-            HIGH_RAM_START..=HIGH_RAM_END => {
-                self.high_ram.write().unwrap().write_word(address, value)
-            }
-            INTERRUPTS_REGISTER => self
-                .interrupt_register
-                .write()
-                .unwrap()
-                .write_word(address, value),
-            _ => panic!("Address {} not handled", address),
+    /// Load boot sequence and map it over the beginning of the ROM
+    pub fn load_boot(&self, path: &Path) -> GbResult<()> {
+        let boot_sequence = fs::read(path).map_err(|source| Error::Io {
+            path: path.into(),
+            source,
+        })?;
+        if boot_sequence.len() != BOOT_SEQUENCE_SIZE {
+            return Err(Error::BootRomSize(boot_sequence.len()));
         }
+
+        let boot_rom = &mut self.boot_rom.write().unwrap();
+        boot_rom.buffer_as_mut().copy_from_slice(&boot_sequence);
+        boot_rom.map();
+        Ok(())
+    }
+
+    /// I/O registers as left by the boot ROM of the model, the boot sequence stays unmapped
+    pub fn skip_boot(&self, model: Model) {
+        *self.io_register.write().unwrap() = IoRegisters::post_boot(model);
+        *self.oam_dma.write().unwrap() = OamDma::post_boot();
+        *self.interrupts.write().unwrap() = Interrupts::post_boot();
+    }
+
+    /// Content of the cartridge battery save, if it has one
+    pub fn save_data(&self) -> Option<Vec<u8>> {
+        self.cartridge.read().unwrap().save_data()
     }
 
-    /// Load boot sequence to the beginning of the ROM
-    pub fn load_boot(&self) -> Result<(), String> {
-        let memory = &mut self.read_only_memory.write().unwrap();
-        let mut boot_sequence = File::open(BOOT_SEQUENCE_PATH).unwrap();
+    /// Restore the cartridge RAM and clock from a battery save
+    pub fn load_save(&self, data: &[u8]) -> GbResult<()> {
+        self.cartridge.write().unwrap().load_save(data)
+    }
+
+    /// Load cartridge, under the boot sequence if mapped
+    pub fn load_cartridge(&self, cartridge: &Cartridge) {
+        *self.cartridge.write().unwrap() = CartridgeMemory::new(cartridge);
+    }
+}
 
-        let boot_size = boot_sequence
-            .read(memory.buffer_as_mut())
-            .map_err(|e| format!("Failed to parse boot sequence : {}", e))?;
-        if boot_size != BOOT_SEQUENCE_SIZE {
-            Err("Invalid read size".to_string())
-        } else {
-            Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gameboy::test_memory::{read_word, write_word};
+
+    /// 128KiB MBC1 cartridge with 8KiB of RAM. Each bank starts with its number.
+    fn create_mbc1_bus() -> MemoryBus {
+        let mut rom = vec![0u8; 0x20000];
+        rom[0x0147] = 0x02;
+        rom[0x0148] = 0x02;
+        rom[0x0149] = 0x02;
+        for bank in 0..8 {
+            rom[bank * 0x4000 + 0x200] = bank as u8;
         }
+
+        let memory_bus = MemoryBus::default();
+        memory_bus.load_cartridge(&Cartridge::from_bytes(rom).unwrap());
+        memory_bus
     }
 
-    /// Load cartridge after the boot sequence.
-    pub fn load_cartridge(&self, cartrige_path: &str) -> Result<(), String> {
-        let memory = &mut self.read_only_memory.write().unwrap();
-        let mut cartridge = File::open(cartrige_path).map_err(|e| e.to_string())?;
-        // "Skip" the first 0x100 bytes
-        cartridge
-            .read(&mut memory.buffer_as_mut()[BOOT_SEQUENCE_SIZE..0x0200])
-            .map_err(|e| format!("Failed to parse cartride : {}", e))?;
+    #[test]
+    fn mbc1_rom_banking() {
+        let memory_bus = create_mbc1_bus();
+        assert_eq!(memory_bus.read_byte(0x0200), 0);
+        assert_eq!(memory_bus.read_byte(0x4200), 1);
 
-        // write content in memoy after boot sequence size
-        cartridge
-            .read(&mut memory.buffer_as_mut()[BOOT_SEQUENCE_SIZE..=ROM_END.into()])
-            .map_err(|e| format!("Failed to parse cartride : {}", e))?;
+        memory_bus.write_byte(0x2000, 0x06);
+        assert_eq!(memory_bus.read_byte(0x4200), 6);
+        // The ROM itself is untouched
+        assert_eq!(memory_bus.read_byte(0x2000), 0);
+    }
 
-        Ok(())
+    #[test]
+    fn boot_rom_overlay() {
+        let memory_bus = create_mbc1_bus();
+        memory_bus.boot_rom.write().unwrap().buffer_as_mut()[0x00] = 0x31;
+        memory_bus.boot_rom.write().unwrap().map();
+        assert_eq!(memory_bus.read_byte(0x0000), 0x31);
+        // Only the first 0x100 bytes are hidden
+        assert_eq!(memory_bus.read_byte(0x0200), 0);
+
+        // Zero does not unmap
+        memory_bus.write_byte(0xFF50, 0x00);
+        assert_eq!(memory_bus.read_byte(0x0000), 0x31);
+
+        memory_bus.write_byte(0xFF50, 0x01);
+        assert_eq!(memory_bus.read_byte(0x0000), 0x00);
+        // Writing 0 again does not remap it
+        memory_bus.write_byte(0xFF50, 0x00);
+        assert_eq!(memory_bus.read_byte(0x0000), 0x00);
+    }
+
+    #[test]
+    fn skip_boot() {
+        let memory_bus = create_mbc1_bus();
+        memory_bus.skip_boot(Model::Dmg);
+        assert_eq!(memory_bus.read_byte(0xFF40), 0x91);
+        assert_eq!(memory_bus.read_byte(0xFF47), 0xFC);
+        assert_eq!(memory_bus.read_byte(0xFF04), 0xAB);
+        assert_eq!(memory_bus.read_byte(0x0000), 0x00);
+
+        memory_bus.skip_boot(Model::Dmg0);
+        assert_eq!(memory_bus.read_byte(0xFF04), 0x18);
+    }
+
+    #[test]
+    fn missing_boot_rom() {
+        let memory_bus = MemoryBus::default();
+        assert!(matches!(
+            memory_bus.load_boot(Path::new("missing.bin")),
+            Err(Error::Io { .. })
+        ));
+        // Cartridge instead of boot ROM
+        assert!(matches!(
+            memory_bus.load_boot(Path::new("etc/cpu_instrs.gb")),
+            Err(Error::BootRomSize(0x10000))
+        ));
+        assert!(memory_bus.load_boot(Path::new("etc/DMG_ROM.bin")).is_ok());
+    }
+
+    #[test]
+    fn unusable_area() {
+        let memory_bus = MemoryBus::default();
+        memory_bus.write_byte(0xFEA0, 0x42);
+        assert_eq!(memory_bus.read_byte(0xFEA0), 0x00);
+        // High byte is P1, whose unused bits read as 1
+        assert_eq!(read_word(&memory_bus, 0xFEFF), 0xFF00);
+    }
+
+    #[test]
+    fn straddling_words() {
+        let memory_bus = MemoryBus::default();
+        // Last byte of the sprite table, first of the unusable area
+        write_word(&memory_bus, 0xFE9F, 0x1234);
+        assert_eq!(memory_bus.read_byte(0xFE9F), 0x34);
+        assert_eq!(read_word(&memory_bus, 0xFE9F), 0x0034);
+
+        // Work RAM banks
+        write_word(&memory_bus, 0xCFFF, 0x1234);
+        assert_eq!(read_word(&memory_bus, 0xCFFF), 0x1234);
+
+        // High RAM and interrupt enable
+        write_word(&memory_bus, 0xFFFE, 0x1234);
+        assert_eq!(memory_bus.read_byte(0xFFFF), 0x12);
+        assert_eq!(read_word(&memory_bus, 0xFFFE), 0x1234);
+
+        // VRAM and cartridge RAM
+        write_word(&memory_bus, 0x9FFF, 0x1234);
+        assert_eq!(read_word(&memory_bus, 0x9FFF), 0x1234);
+    }
+
+    #[test]
+    fn vram_words() {
+        let memory_bus = MemoryBus::default();
+        // Between tile data and the first tile map
+        write_word(&memory_bus, 0x97FF, 0x1234);
+        assert_eq!(read_word(&memory_bus, 0x97FF), 0x1234);
+        // Tile maps
+        write_word(&memory_bus, 0x9BFF, 0xABCD);
+        assert_eq!(read_word(&memory_bus, 0x9BFF), 0xABCD);
+        assert_eq!(memory_bus.vram().tile_map_1[0x3FF], 0xCD);
+        assert_eq!(memory_bus.vram().tile_map_2[0x000], 0xAB);
+    }
+
+    #[test]
+    fn echo_ram() {
+        let memory_bus = MemoryBus::default();
+        // Work RAM seen through the echo
+        memory_bus.write_byte(0xC000, 0x42);
+        assert_eq!(memory_bus.read_byte(0xE000), 0x42);
+        write_word(&memory_bus, 0xDDFE, 0x1234);
+        assert_eq!(read_word(&memory_bus, 0xFDFE), 0x1234);
+
+        // Echo seen through the work RAM, across both banks
+        write_word(&memory_bus, 0xEFFF, 0xABCD);
+        assert_eq!(memory_bus.read_byte(0xCFFF), 0xCD);
+        assert_eq!(memory_bus.read_byte(0xD000), 0xAB);
+        memory_bus.write_byte(0xFDFF, 0x24);
+        assert_eq!(memory_bus.read_byte(0xDDFF), 0x24);
+
+        // 0xDE00-0xDFFF is not mirrored
+        memory_bus.write_byte(0xDE00, 0x42);
+        assert_eq!(memory_bus.read_byte(0xFE00), 0x00);
+    }
+
+    #[test]
+    fn oam_dma() {
+        let memory_bus = MemoryBus::default();
+        for offset in 0..0xA0 {
+            memory_bus.write_byte(0xC100 + offset, offset as u8);
+        }
+        memory_bus.write_byte(0xFF80, 0x42);
+
+        memory_bus.write_byte(0xFF46, 0xC1);
+        // Only the I/O registers, the high RAM and IE are reachable
+        assert_eq!(memory_bus.read_byte(0xC100), 0xFF);
+        assert_eq!(memory_bus.read_byte(0xFF80), 0x42);
+        memory_bus.write_byte(0xC100, 0x24);
+        write_word(&memory_bus, 0xFF81, 0x1234);
+        assert_eq!(read_word(&memory_bus, 0xFF81), 0x1234);
+        assert_eq!(read_word(&memory_bus, 0xFF7F), 0x42FF);
+
+        memory_bus.tick(4 * 159);
+        assert_eq!(memory_bus.read_byte(0xFE00), 0xFF);
+        assert_eq!(memory_bus.read_byte_unblocked(0xFE9E), 0x9E);
+        assert_eq!(memory_bus.read_byte_unblocked(0xFE9F), 0x00);
+
+        memory_bus.tick(4);
+        assert_eq!(memory_bus.read_byte(0xC100), 0x00);
+        for offset in 0..0xA0 {
+            assert_eq!(memory_bus.read_byte(0xFE00 + offset), offset as u8);
+        }
+    }
+
+    #[test]
+    fn oam_dma_restart() {
+        let memory_bus = MemoryBus::default();
+        for offset in 0..0xA0 {
+            memory_bus.write_byte(0xC100 + offset, 0x11);
+            memory_bus.write_byte(0xC200 + offset, 0x22);
+        }
+
+        memory_bus.write_byte(0xFF46, 0xC1);
+        memory_bus.tick(4 * 80);
+        // As a routine running from the high RAM does
+        memory_bus.write_byte(0xFF46, 0xC2);
+        assert_eq!(memory_bus.read_byte(0xFF46), 0xC2);
+        memory_bus.write_byte(0xFFFF, 0x04);
+        assert_eq!(memory_bus.read_byte(0xFFFF), 0x04);
+
+        // The new transfer starts over from the first byte
+        memory_bus.tick(4 * 159);
+        assert_eq!(memory_bus.read_byte(0xFE00), 0xFF);
+        memory_bus.tick(4);
+        for offset in 0..0xA0 {
+            assert_eq!(memory_bus.read_byte(0xFE00 + offset), 0x22);
+        }
+    }
+
+    #[test]
+    fn mbc1_ram() {
+        let memory_bus = create_mbc1_bus();
+        memory_bus.write_byte(0xA000, 0x42);
+        assert_eq!(memory_bus.read_byte(0xA000), 0xFF);
+
+        memory_bus.write_byte(0x0000, 0x0A);
+        write_word(&memory_bus, 0xA000, 0x1234);
+        assert_eq!(read_word(&memory_bus, 0xA000), 0x1234);
+
+        memory_bus.write_byte(0x0000, 0x00);
+        assert_eq!(memory_bus.read_byte(0xA000), 0xFF);
+    }
+
+    #[test]
+    fn timer_interrupt() {
+        let memory_bus = MemoryBus::default();
+        memory_bus.write_byte(0xFFFF, 0x04);
+        memory_bus.write_byte(0xFF07, 0x05);
+        memory_bus.write_byte(0xFF05, 0xFF);
+        memory_bus.tick(16);
+        assert_eq!(memory_bus.pending_interrupt(), None);
+        memory_bus.tick(4);
+        assert_eq!(memory_bus.pending_interrupt(), Some(Interrupt::Timer));
+        assert_eq!(memory_bus.read_byte(0xFF0F), 0xE4);
     }
 }
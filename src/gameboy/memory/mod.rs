@@ -1,3 +1,9 @@
+/// OAM DMA transfer
+mod dma;
+/// Interrupt controller
+mod interrupts;
+/// I/O registers dispatch
+mod io;
 /// Expected behavior of memory zones
 mod memory_behavior;
 /// Different memory zones are defined here
@@ -10,22 +16,25 @@ mod vram;
 
 use std::sync::Arc;
 pub type SharedMemory = Arc<memorybus::MemoryBus>;
+pub use interrupts::Interrupt;
+pub use io::{Button, SerialSink};
 pub use memorybus::MemoryBus;
 pub use vram::VideoRam;
 
-const BOOT_SEQUENCE_PATH: &str = "etc/DMG_ROM.bin";
+const BOOT_SEQUENCE_START: u16 = 0x0000;
+const BOOT_SEQUENCE_END: u16 = 0x00FF;
 const BOOT_SEQUENCE_SIZE: usize = 0x0100;
+/// Writing a nonzero value unmaps the boot sequence
+const BOOT_SEQUENCE_DISABLE: u16 = 0xFF50;
 
 const ROM_START: u16 = 0x0000;
-const ROM_END: u16 = 0x7999;
-const ROM_SIZE: usize = 0x8000;
+const ROM_END: u16 = 0x7FFF;
 
 const VRAM_START: u16 = 0x8000;
 const VRAM_END: u16 = 0x9FFF;
 
 const EXT_RAM_START: u16 = 0xA000;
 const EXT_RAM_END: u16 = 0xBFFF;
-const EXT_RAM_SIZE: usize = 0x2000;
 
 const BANK_1_START: u16 = 0xC000;
 const BANK_1_END: u16 = 0xCFFF;
@@ -35,21 +44,26 @@ const BANK_0_START: u16 = 0xD000;
 const BANK_0_END: u16 = 0xDFFF;
 const BANK_0_SIZE: usize = 0x1000;
 
+/// Mirror of 0xC000-0xDDFF
 const ECHO_RAM_START: u16 = 0xE000;
 const ECHO_RAM_END: u16 = 0xFDFF;
-const ECHO_RAM_SIZE: usize = 0x1e00;
+const ECHO_RAM_OFFSET: u16 = 0x2000;
 
 const SPRITE_TABLE_START: u16 = 0xFE00;
 const SPRITE_TABLE_END: u16 = 0xFE9F;
 const SPRITE_TABLE_SIZE: usize = 0x00a0;
 
+/// Prohibited area : reads return 0, writes are ignored
+const UNUSABLE_START: u16 = 0xFEA0;
+const UNUSABLE_END: u16 = 0xFEFF;
+
 const IO_REGISTER_START: u16 = 0xFF00;
+/// Writing starts an OAM DMA transfer
+const OAM_DMA_REGISTER: u16 = 0xFF46;
 const IO_REGISTER_END: u16 = 0xFF7F;
-const IO_REGISTER_SIZE: usize = 0x0080;
 
 const HIGH_RAM_START: u16 = 0xFF80;
 const HIGH_RAM_END: u16 = 0xFFFE;
-const HIGH_RAM_SIZE: usize = 0x007E;
+const HIGH_RAM_SIZE: usize = 0x007F;
 
 const INTERRUPTS_REGISTER: u16 = 0xFFFF;
-const INTERRUPTS_REGISTER_SIZE: usize = 1;
@@ -25,13 +25,19 @@ impl Default for VideoRam {
 }
 
 impl VideoRam {
-    /// Return Tile plus the line offset, computed from the address
-    fn get_tile_with_line_offset(&mut self, address: u16) -> (&mut Tile, usize) {
+    /// Return Tile index plus the line offset, computed from the address
+    fn tile_index_with_line_offset(address: u16) -> (usize, usize) {
         let real_offset = (address - VRAM_START) as usize;
         let quotient = real_offset.div_euclid(std::mem::size_of::<Tile>());
         // We divide by 2 to match the 2 bytes wide line
         let remain = real_offset.rem_euclid(std::mem::size_of::<Tile>()) / 2;
-        (&mut self.tile_data[quotient], remain)
+        (quotient, remain)
+    }
+
+    /// Return Tile plus the line offset, computed from the address
+    fn get_tile_with_line_offset(&mut self, address: u16) -> (&mut Tile, usize) {
+        let (index, line_offset) = Self::tile_index_with_line_offset(address);
+        (&mut self.tile_data[index], line_offset)
     }
 }
 
@@ -46,13 +52,6 @@ pub struct Tile {
 }
 
 impl Tile {
-    /// Write the word to the corresponding line in the Tile.
-    fn write_line(&mut self, line_offset: usize, value: u16) {
-        let [low_value, high_value] = value.to_be_bytes();
-        self.higher_bytes[line_offset] = high_value;
-        self.lower_bytes[line_offset] = low_value;
-    }
-
     fn write_higher_byte(&mut self, line_offset: usize, value: u8) {
         log::trace!(
             "{:08b}|line {}|tile {:?}",
@@ -91,19 +90,20 @@ impl Memory for VideoRam {
         panic!("Do not access this directly")
     }
 
-    fn write_word(&mut self, address: u16, value: u16) {
+    fn read_byte(&self, address: u16) -> u8 {
         match address {
             0x8000..=0x97FF => {
-                let (tile, line_offset) = self.get_tile_with_line_offset(address);
-                tile.write_line(line_offset, value);
-            }
-            0x9800..=0x09BFF => {
-                todo!()
-            }
-            0x9C00..=0x9FFF => {
-                todo!()
+                let (index, line_offset) = Self::tile_index_with_line_offset(address);
+                let tile = &self.tile_data[index];
+                if address.rem_euclid(2) == 0 {
+                    tile.lower_bytes[line_offset]
+                } else {
+                    tile.higher_bytes[line_offset]
+                }
             }
-            _ => unimplemented!(),
+            0x9800..=0x9BFF => self.tile_map_1[address as usize - 0x9800],
+            0x9C00..=0x9FFF => self.tile_map_2[address as usize - 0x9C00],
+            _ => unreachable!(),
         }
     }
 
@@ -120,12 +120,12 @@ impl Memory for VideoRam {
                     tile.write_higher_byte(line_offset, value);
                 }
             }
-            0x9800..=0x09BFF => {
+            0x9800..=0x9BFF => {
                 log::debug!("Bind tilemap_1[{}] to tile {}", address - 0x9800, value);
                 self.tile_map_1[address as usize - 0x9800] = value;
             }
             0x9C00..=0x9FFF => {
-                log::debug!("Bind tilemap_2[{}] to tile {}", address - 0x9800, value);
+                log::debug!("Bind tilemap_2[{}] to tile {}", address - 0x9C00, value);
                 self.tile_map_2[address as usize - 0x9C00] = value;
             }
             _ => unreachable!(),
@@ -166,10 +166,14 @@ mod tests {
 
     #[test]
     fn write_tile() {
-        let mut tile = Tile::default();
-        tile.write_line(0, 0x3C7E);
-        assert_eq!(tile.higher_bytes[0], 0x7E);
-        assert_eq!(tile.lower_bytes[0], 0x3C);
+        let mut vram = VideoRam::default();
+        // The low bits of a line are at the even address
+        vram.write_byte(0x8000, 0x7E);
+        vram.write_byte(0x8001, 0x3C);
+        assert_eq!(vram.tile_data[0].higher_bytes[0], 0x3C);
+        assert_eq!(vram.tile_data[0].lower_bytes[0], 0x7E);
+        assert_eq!(vram.read_byte(0x8000), 0x7E);
+        assert_eq!(vram.read_byte(0x8001), 0x3C);
     }
 
     #[test]
@@ -1,16 +1,34 @@
 mod arithmetictarget;
+#[cfg(test)]
+mod assembler;
+pub mod cartridge;
 mod cpu;
+pub mod disassembler;
+mod error;
 mod flagsregister;
 mod gpu;
 mod instruction;
 mod memory;
+mod model;
+mod options;
 mod registers;
+#[cfg(test)]
+mod test_memory;
+#[cfg(test)]
+mod test_output;
 
+use cartridge::{Cartridge, CartridgeHeader, SaveFile};
 use cpu::Cpu;
+pub use error::Error;
 use gpu::Gpu;
-use memory::MemoryBus;
+pub use memory::SerialSink;
+use memory::{Button, MemoryBus, SharedMemory};
+pub use model::Model;
+pub use options::LoadOptions;
+use std::fs::File;
+use std::io::LineWriter;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use glium::{
     texture::{MipmapsOption, UncompressedFloatFormat},
@@ -20,8 +38,11 @@ use glium::{
 // 4MHz frequency - or 8MHz in CGB double frequency mode.
 const CPU_TICK_DURATION: std::time::Duration = Duration::from_nanos(250);
 
+/// Battery saves are also written while running, in case the emulator is killed
+const SAVE_INTERVAL: Duration = Duration::from_secs(10);
+
 /// Custom result type, for internal purpose mostly
-type GbResult<T> = Result<T, String>;
+type GbResult<T> = Result<T, Error>;
 
 use winit::{
     event::{ElementState, Event, KeyEvent, WindowEvent},
@@ -39,29 +60,136 @@ use winit::{
 pub struct Gameboy {
     cpu: Cpu,
     gpu: Gpu,
+    bus: SharedMemory,
+    /// Header of the loaded cartridge, if any
+    header: Option<CartridgeHeader>,
+    /// Save file of cartridges with a battery
+    save: Option<SaveFile>,
 }
 
 impl Gameboy {
-    pub fn new() -> GbResult<Self> {
-        let bus = Arc::new(MemoryBus::new()?);
-        Ok(Self {
+    /// Run the boot sequence only, without cartridge
+    pub fn new(options: &LoadOptions) -> GbResult<Self> {
+        let bus = Arc::new(MemoryBus::default());
+        let mut gameboy = Self {
             cpu: Cpu::new(bus.clone()),
             gpu: Gpu::new(bus.clone()),
-        })
+            bus,
+            header: None,
+            save: None,
+        };
+        gameboy.boot(options)?;
+        gameboy.open_trace(options)?;
+        Ok(gameboy)
     }
 
+    /// Load the cartridge, refusing the ones that can't be emulated.
     pub fn load(rom_path: &str) -> GbResult<Self> {
-        let bus = Arc::new(MemoryBus::load(rom_path)?);
-        Ok(Self {
+        Self::load_with_options(rom_path, &LoadOptions::default())
+    }
+
+    pub fn load_with_options(rom_path: &str, options: &LoadOptions) -> GbResult<Self> {
+        let mut cartridge = Cartridge::from_file(rom_path)?;
+
+        let checksum_errors = cartridge.checksum_errors();
+        for error in &checksum_errors {
+            log::warn!("{}", error);
+        }
+        if options.fix_checksums && !checksum_errors.is_empty() {
+            log::info!("Fixing cartridge checksums");
+            cartridge.fix_checksums();
+        }
+
+        let header = cartridge.header();
+        log::info!(
+            "Cartridge {:?} : {:?}, {} KiB ROM, {} KiB RAM",
+            header.title,
+            header.cartridge_type.mapper,
+            header.rom_size / 1024,
+            header.ram_size / 1024
+        );
+        cartridge.check_supported()?;
+
+        let bus = Arc::new(MemoryBus::load(&cartridge));
+
+        let save = if cartridge.header().cartridge_type.battery {
+            let mut save = SaveFile::for_rom(rom_path);
+            let data = save.read().map_err(|source| Error::Io {
+                path: save.path().into(),
+                source,
+            })?;
+            if let Some(data) = data {
+                log::info!("Loading save {}", save.path().display());
+                bus.load_save(&data)?;
+            }
+            Some(save)
+        } else {
+            None
+        };
+
+        let mut gameboy = Self {
             cpu: Cpu::new(bus.clone()),
             gpu: Gpu::new(bus.clone()),
-        })
+            bus,
+            header: Some(cartridge.header().clone()),
+            save,
+        };
+        gameboy.boot(options)?;
+        gameboy.open_trace(options)?;
+        Ok(gameboy)
+    }
+
+    /// Map the boot ROM, or set the state it leaves when there is none
+    fn boot(&mut self, options: &LoadOptions) -> GbResult<()> {
+        match &options.boot_rom {
+            Some(path) => self.bus.load_boot(path),
+            None => {
+                log::info!("Skipping boot sequence, as {:?}", options.model);
+                let header_checksum = self.header.as_ref().map_or(0, |h| h.header_checksum);
+                self.cpu.skip_boot(options.model, header_checksum);
+                self.bus.skip_boot(options.model);
+                Ok(())
+            }
+        }
+    }
+
+    /// Trace every instruction run by the CPU, when asked to
+    fn open_trace(&mut self, options: &LoadOptions) -> GbResult<()> {
+        if let Some(path) = &options.trace {
+            let file = File::create(path).map_err(|source| Error::Trace {
+                path: path.clone(),
+                source,
+            })?;
+            log::info!("Tracing instructions to {}", path.display());
+            // Flushed line by line, the CPU thread is never joined
+            self.cpu.set_trace(Box::new(LineWriter::new(file)));
+            // The reference logs would otherwise diverge on the first LY polling
+            self.bus.stub_ly();
+        }
+        Ok(())
+    }
+
+    /// Collect the bytes the game sends over the serial port, such as the blargg test results
+    pub fn set_serial_sink(&self, sink: SerialSink) {
+        self.bus.set_serial_sink(sink);
+    }
+
+    /// Header of the loaded cartridge. None when running the boot sequence only.
+    pub fn header(&self) -> Option<&CartridgeHeader> {
+        self.header.as_ref()
     }
 
     pub fn run(self) {
         println!("Run");
 
-        let Gameboy { mut cpu, mut gpu } = self;
+        let Gameboy {
+            mut cpu,
+            mut gpu,
+            bus,
+            mut save,
+            ..
+        } = self;
+        let mut last_save = Instant::now();
 
         let event_loop = EventLoopBuilder::new()
             .build()
@@ -102,26 +230,73 @@ impl Gameboy {
             // let _next_frame_time = std::time::Instant::now() +
             //     std::time::Duration::from_secs(2);
 
-            // TODO : input event should write to registers
             match ev {
-                Event::WindowEvent { event, .. } => match event {
-                    WindowEvent::CloseRequested
-                    | WindowEvent::KeyboardInput {
-                        event:
-                            KeyEvent {
-                                state: ElementState::Pressed,
-                                logical_key: Key::Named(NamedKey::Escape),
-                                ..
-                            },
-                        ..
-                    } => window_target.exit(),
-                    _ => (),
-                },
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::CloseRequested
+                        | WindowEvent::KeyboardInput {
+                            event:
+                                KeyEvent {
+                                    state: ElementState::Pressed,
+                                    logical_key: Key::Named(NamedKey::Escape),
+                                    ..
+                                },
+                            ..
+                        },
+                    ..
+                } => window_target.exit(),
                 Event::AboutToWait => {
                     window.request_redraw();
+                    if last_save.elapsed() >= SAVE_INTERVAL {
+                        write_save(&bus, save.as_mut());
+                        last_save = Instant::now();
+                    }
+                }
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::KeyboardInput {
+                            event:
+                                KeyEvent {
+                                    state, logical_key, ..
+                                },
+                            ..
+                        },
+                    ..
+                } => {
+                    if let Some(button) = button_for(&logical_key) {
+                        bus.set_button(button, state == ElementState::Pressed);
+                    }
                 }
+                Event::LoopExiting => write_save(&bus, save.as_mut()),
                 _ => (),
             }
         });
     }
 }
+
+/// Arrows for the directions, X and Z for A and B, Enter and Backspace for Start and Select
+fn button_for(key: &Key) -> Option<Button> {
+    match key {
+        Key::Named(NamedKey::ArrowRight) => Some(Button::Right),
+        Key::Named(NamedKey::ArrowLeft) => Some(Button::Left),
+        Key::Named(NamedKey::ArrowUp) => Some(Button::Up),
+        Key::Named(NamedKey::ArrowDown) => Some(Button::Down),
+        Key::Named(NamedKey::Enter) => Some(Button::Start),
+        Key::Named(NamedKey::Backspace) => Some(Button::Select),
+        Key::Character(c) => match c.to_lowercase().as_str() {
+            "x" => Some(Button::A),
+            "z" => Some(Button::B),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Write the battery save, if the cartridge has one
+fn write_save(bus: &MemoryBus, save: Option<&mut SaveFile>) {
+    if let (Some(save), Some(data)) = (save, bus.save_data()) {
+        if let Err(e) = save.write(&data) {
+            log::error!("Failed to write save {} : {}", save.path().display(), e);
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use super::Error;
+use std::str::FromStr;
+
+/// Game Boy hardware revision.
+/// Each boot ROM leaves the CPU and I/O registers in a slightly different state.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub enum Model {
+    /// Early Game Boy, with the first boot ROM revision
+    Dmg0,
+    /// Game Boy
+    #[default]
+    Dmg,
+    /// Game Boy Pocket
+    Mgb,
+    /// Super Game Boy
+    Sgb,
+    /// Super Game Boy 2
+    Sgb2,
+}
+
+impl FromStr for Model {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.to_ascii_lowercase().as_str() {
+            "dmg0" => Ok(Model::Dmg0),
+            "dmg" => Ok(Model::Dmg),
+            "mgb" => Ok(Model::Mgb),
+            "sgb" => Ok(Model::Sgb),
+            "sgb2" => Ok(Model::Sgb2),
+            _ => Err(Error::UnknownModel(name.to_string())),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use super::Model;
+use std::path::PathBuf;
+
+/// Options used when loading a cartridge
+#[derive(Clone, Debug, Default)]
+pub struct LoadOptions {
+    /// Rewrite bad header and global checksums in memory instead of only warning.
+    /// Without it, the boot ROM locks up on a bad header checksum.
+    pub fix_checksums: bool,
+    /// Boot ROM run before the cartridge.
+    /// Without it, the cartridge starts at 0x0100 with the registers left by the boot ROM
+    /// of `model`.
+    pub boot_rom: Option<PathBuf>,
+    pub model: Model,
+    /// File receiving the state before each instruction, in the Gameboy Doctor format.
+    /// As in its reference logs, LY then always reads 0x90.
+    pub trace: Option<PathBuf>,
+}
@@ -0,0 +1,15 @@
+use super::memory::MemoryBus;
+
+/// Little endian word, read byte by byte as the CPU does
+pub fn read_word(memory_bus: &MemoryBus, address: u16) -> u16 {
+    u16::from_le_bytes([
+        memory_bus.read_byte(address),
+        memory_bus.read_byte(address.wrapping_add(1)),
+    ])
+}
+
+pub fn write_word(memory_bus: &MemoryBus, address: u16, value: u16) {
+    let [low, high] = value.to_le_bytes();
+    memory_bus.write_byte(address, low);
+    memory_bus.write_byte(address.wrapping_add(1), high);
+}
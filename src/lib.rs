@@ -1,3 +1,3 @@
 mod gameboy;
 
-pub use gameboy::Gameboy;
+pub use gameboy::{cartridge, disassembler, Error, Gameboy, LoadOptions, Model, SerialSink};
@@ -1,22 +1,77 @@
 use std::env;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process;
 mod logging;
-use gb::Gameboy;
+use gb::{Gameboy, LoadOptions};
+
+/// Boot ROM used when none is given
+const DEFAULT_BOOT_ROM: &str = "etc/DMG_ROM.bin";
 
 fn main() {
     // Init logging
     simple_logging::log_to_file("test.log", logging::log_level())
         .expect("Failed to create logging env");
-    let filename = env::args().nth(1);
+    let mut options = LoadOptions::default();
+    let mut filename = None;
+    let mut skip_boot = false;
+    let mut print_serial = false;
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--fix-checksums" => options.fix_checksums = true,
+            "--skip-boot" => skip_boot = true,
+            "--serial" => print_serial = true,
+            "--boot-rom" => {
+                options.boot_rom = Some(PathBuf::from(value(&mut args, "--boot-rom", "a path")))
+            }
+            "--trace" => options.trace = Some(PathBuf::from(value(&mut args, "--trace", "a path"))),
+            "--model" => {
+                options.model = value(&mut args, "--model", "dmg0, dmg, mgb, sgb or sgb2")
+                    .parse()
+                    .unwrap_or_else(|e| exit(e))
+            }
+            _ if arg.starts_with("--") => exit(format!("Unknown option {}", arg)),
+            _ if filename.is_some() => exit(format!("Unexpected argument {}", arg)),
+            _ => filename = Some(arg),
+        }
+    }
+
+    if skip_boot {
+        options.boot_rom = None;
+    } else if options.boot_rom.is_none() {
+        if Path::new(DEFAULT_BOOT_ROM).exists() {
+            options.boot_rom = Some(PathBuf::from(DEFAULT_BOOT_ROM));
+        } else {
+            log::warn!("No boot ROM found at {}, skipping boot", DEFAULT_BOOT_ROM);
+        }
+    }
 
     let gameboy = if let Some(filename) = filename {
         // load ROM
         log::info!("Run with ROM {}", filename);
-        Gameboy::load(&filename).unwrap()
+        Gameboy::load_with_options(&filename, &options)
     } else {
         // Only the Bootstrap
         log::info!("Run without ROM");
-        Gameboy::new().unwrap()
+        Gameboy::new(&options)
     };
 
+    let gameboy = gameboy.unwrap_or_else(|e| exit(e));
+    if print_serial {
+        gameboy.set_serial_sink(Box::new(std::io::stdout()));
+    }
     gameboy.run()
 }
+
+/// Value following a flag, which is a usage error when missing
+fn value(args: &mut impl Iterator<Item = String>, flag: &str, expected: &str) -> String {
+    args.next()
+        .unwrap_or_else(|| exit(format!("{} expects {}", flag, expected)))
+}
+
+fn exit(error: impl fmt::Display) -> ! {
+    log::error!("{}", error);
+    eprintln!("{}", error);
+    process::exit(1)
+}